@@ -0,0 +1,463 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use csv::StringRecord;
+use crate::value;
+
+/// Aggregate functions accepted by --agg
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+    Stdp,
+    Stds,
+    Var,
+    CountDistinct,
+    ApproxCountDistinct,
+    Mode,
+}
+
+impl Function {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "sum" => Ok(Function::Sum),
+            "avg" => Ok(Function::Avg),
+            "min" => Ok(Function::Min),
+            "max" => Ok(Function::Max),
+            "count" => Ok(Function::Count),
+            "stdp" => Ok(Function::Stdp),
+            "stds" => Ok(Function::Stds),
+            "var" => Ok(Function::Var),
+            "count_distinct" => Ok(Function::CountDistinct),
+            "approx_count_distinct" => Ok(Function::ApproxCountDistinct),
+            "mode" => Ok(Function::Mode),
+            _ => Err(format!("Unknown aggregate '{}', expected one of: sum, avg, min, max, count, stdp, stds, var, \
+                              count_distinct, approx_count_distinct, mode", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Sum => "sum",
+            Function::Avg => "avg",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::Count => "count",
+            Function::Stdp => "stdp",
+            Function::Stds => "stds",
+            Function::Var => "var",
+            Function::CountDistinct => "count_distinct",
+            Function::ApproxCountDistinct => "approx_count_distinct",
+            Function::Mode => "mode",
+        }
+    }
+}
+
+/// Running state of an aggregate over one column. Values that are not
+/// numbers are skipped by the numeric aggregates, and count counts the
+/// non-empty fields
+pub enum Aggregator {
+    Sum(f64),
+    Avg { sum: f64, count: usize },
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Count(usize),
+    // Welford's online algorithm, so the deviation is computed in one pass
+    // without keeping the values around
+    Deviation { function: Function, count: usize, mean: f64, m2: f64 },
+    Distinct(HashSet<String>),
+    ApproxDistinct(HyperLogLog),
+    // Occurrences and first position of each value, ties go to the first seen
+    Mode(HashMap<String, (usize, usize)>),
+}
+
+impl Aggregator {
+    pub fn new(function: Function) -> Self {
+        match function {
+            Function::Sum => Aggregator::Sum(0.0),
+            Function::Avg => Aggregator::Avg { sum: 0.0, count: 0 },
+            Function::Min => Aggregator::Min(None),
+            Function::Max => Aggregator::Max(None),
+            Function::Count => Aggregator::Count(0),
+            Function::Stdp | Function::Stds | Function::Var => {
+                Aggregator::Deviation { function, count: 0, mean: 0.0, m2: 0.0 }
+            },
+            Function::CountDistinct => Aggregator::Distinct(HashSet::new()),
+            Function::ApproxCountDistinct => Aggregator::ApproxDistinct(HyperLogLog::new()),
+            Function::Mode => Aggregator::Mode(HashMap::new()),
+        }
+    }
+
+    pub fn update(&mut self, field: &str) {
+        // Counting aggregates take any non-empty field
+        match self {
+            Aggregator::Count(count) => {
+                if !field.trim().is_empty() {
+                    *count += 1;
+                }
+                return;
+            },
+            Aggregator::Distinct(values) => {
+                if !field.trim().is_empty() && !values.contains(field) {
+                    values.insert(String::from(field));
+                }
+                return;
+            },
+            Aggregator::ApproxDistinct(hyper_log_log) => {
+                if !field.trim().is_empty() {
+                    hyper_log_log.insert(field);
+                }
+                return;
+            },
+            Aggregator::Mode(values) => {
+                if !field.trim().is_empty() {
+                    let position = values.len();
+                    values.entry(String::from(field)).or_insert((0, position)).0 += 1;
+                }
+                return;
+            },
+            _ => {},
+        }
+
+        let Some(number) = value::parse_number(field) else {
+            return;
+        };
+        match self {
+            Aggregator::Sum(sum) => *sum += number,
+            Aggregator::Avg { sum, count } => {
+                *sum += number;
+                *count += 1;
+            },
+            Aggregator::Min(min) => *min = Some(min.map_or(number, |min| min.min(number))),
+            Aggregator::Max(max) => *max = Some(max.map_or(number, |max| max.max(number))),
+            Aggregator::Count(_) | Aggregator::Distinct(_) | Aggregator::ApproxDistinct(_) | Aggregator::Mode(_) => {},
+            Aggregator::Deviation { count, mean, m2, .. } => {
+                *count += 1;
+                let delta = number - *mean;
+                *mean += delta / *count as f64;
+                *m2 += delta * (number - *mean);
+            },
+        }
+    }
+
+    /// Final value, empty when there was nothing to aggregate
+    pub fn result(&self) -> String {
+        match self {
+            Aggregator::Sum(sum) => sum.to_string(),
+            Aggregator::Avg { sum, count } => match count {
+                0 => String::new(),
+                _ => (sum / *count as f64).to_string(),
+            },
+            Aggregator::Min(min) => min.map(|min| min.to_string()).unwrap_or_default(),
+            Aggregator::Max(max) => max.map(|max| max.to_string()).unwrap_or_default(),
+            Aggregator::Count(count) => count.to_string(),
+            // The population deviation needs one value, the sample ones two
+            Aggregator::Deviation { function, count, m2, .. } => match (function, *count) {
+                (Function::Stdp, 1..) => (m2 / *count as f64).sqrt().to_string(),
+                (Function::Stds, 2..) => (m2 / (*count - 1) as f64).sqrt().to_string(),
+                (Function::Var, 2..) => (m2 / (*count - 1) as f64).to_string(),
+                _ => String::new(),
+            },
+            Aggregator::Distinct(values) => values.len().to_string(),
+            Aggregator::ApproxDistinct(hyper_log_log) => hyper_log_log.estimate().to_string(),
+            // The most common value followed by its count, like "paris (3)"
+            Aggregator::Mode(values) => values.iter()
+                .max_by(|(_, (count_a, position_a)), (_, (count_b, position_b))| {
+                    count_a.cmp(count_b).then(position_b.cmp(position_a))
+                })
+                .map(|(value, (count, _))| format!("{} ({})", value, count))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// 2^12 registers, for a standard error around 1.6%
+const HYPER_LOG_LOG_BITS: u32 = 12;
+
+/// HyperLogLog sketch estimating the number of distinct values in constant
+/// memory, for columns too big to keep every value in a HashSet
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self { registers: vec![0; 1 << HYPER_LOG_LOG_BITS] }
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        // The first bits pick the register, which keeps the longest run of
+        // leading zeros seen in the remaining bits
+        let register = (hash >> (64 - HYPER_LOG_LOG_BITS)) as usize;
+        let rank = ((hash << HYPER_LOG_LOG_BITS) | (1 << (HYPER_LOG_LOG_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&rank| 2f64.powi(-(rank as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        // Linear counting is more accurate for small cardinalities
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+        estimate.round() as u64
+    }
+}
+
+/// One aggregate of --agg, like "sum:amount". Without a column it's a row count
+pub struct AggSpec {
+    pub function: Function,
+    pub column: Option<usize>,
+    pub name: String,  // output header, like "sum_amount"
+}
+
+impl AggSpec {
+    /// Parses a comma separated list of "function:column" aggregates. A plain
+    /// function applies to each of the default columns, except for a plain
+    /// "count" without default columns, which counts the rows
+    pub fn parse_list(agg_str: &str, headers: &StringRecord,
+                      default_columns: &[usize]) -> Result<Vec<AggSpec>, String> {
+        let mut specs = Vec::new();
+        for spec_str in agg_str.split(',') {
+            match spec_str.split_once(':') {
+                Some((function, column_name)) => {
+                    let column_name = column_name.trim();
+                    let column = headers.iter().position(|h| h == column_name)
+                        .ok_or_else(|| format!("Unknown column '{}' in --agg", column_name))?;
+                    let function = Function::parse(function)?;
+                    specs.push(AggSpec { function, column: Some(column), name: format!("{}_{}", function.name(), column_name) });
+                },
+                None => {
+                    let function = Function::parse(spec_str)?;
+                    if default_columns.is_empty() {
+                        if function != Function::Count {
+                            return Err(format!("Aggregate '{}' needs a column, e.g. {}:amount", spec_str, function.name()));
+                        }
+                        specs.push(AggSpec { function, column: None, name: String::from(function.name()) });
+                    }
+                    for &column in default_columns {
+                        specs.push(AggSpec { function, column: Some(column), name: format!("{}_{}", function.name(), &headers[column]) });
+                    }
+                },
+            }
+        }
+        Ok(specs)
+    }
+
+    fn update(&self, aggregator: &mut Aggregator, record: &StringRecord) {
+        match self.column {
+            Some(column) => aggregator.update(record.get(column).unwrap_or_default()),
+            // Row counts see every row as a non-empty field
+            None => aggregator.update("1"),
+        }
+    }
+}
+
+/// Aggregates computed per group of rows sharing the same key columns.
+/// Groups are kept in the order they first appear. Without key columns all
+/// the rows are a single group
+pub struct GroupBy {
+    key_columns: Vec<usize>,
+    specs: Vec<AggSpec>,
+    group_indices: HashMap<Vec<String>, usize>,
+    keys: Vec<Vec<String>>,
+    aggregators: Vec<Vec<Aggregator>>,
+}
+
+impl GroupBy {
+    pub fn new(key_columns: Vec<usize>, specs: Vec<AggSpec>) -> Self {
+        Self {
+            key_columns,
+            specs,
+            group_indices: HashMap::new(),
+            keys: Vec::new(),
+            aggregators: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        let key: Vec<String> = self.key_columns.iter()
+            .map(|&column| String::from(record.get(column).unwrap_or_default()))
+            .collect();
+        let group_index = match self.group_indices.get(&key) {
+            Some(&group_index) => group_index,
+            None => {
+                self.group_indices.insert(key.clone(), self.keys.len());
+                self.keys.push(key);
+                self.aggregators.push(self.specs.iter().map(|spec| Aggregator::new(spec.function)).collect());
+                self.keys.len() - 1
+            },
+        };
+        for (spec, aggregator) in self.specs.iter().zip(self.aggregators[group_index].iter_mut()) {
+            spec.update(aggregator, record);
+        }
+    }
+
+    /// Group key headers followed by one header per aggregate
+    pub fn headers(&self, headers: &StringRecord) -> Vec<String> {
+        self.key_columns.iter().map(|&column| String::from(&headers[column]))
+            .chain(self.specs.iter().map(|spec| spec.name.clone()))
+            .collect()
+    }
+
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        // The single group of an ungrouped aggregate is reported even without rows
+        if self.key_columns.is_empty() && self.keys.is_empty() {
+            return vec![self.specs.iter().map(|spec| Aggregator::new(spec.function).result()).collect()];
+        }
+        self.keys.iter().zip(&self.aggregators)
+            .map(|(key, aggregators)| key.iter().cloned().chain(aggregators.iter().map(Aggregator::result)).collect())
+            .collect()
+    }
+}
+
+/// Cumulative aggregates appended to each displayed row, for --running
+pub struct Running {
+    specs: Vec<AggSpec>,
+    aggregators: Vec<Aggregator>,
+}
+
+impl Running {
+    pub fn new(specs: Vec<AggSpec>) -> Self {
+        let aggregators = specs.iter().map(|spec| Aggregator::new(spec.function)).collect();
+        Self { specs, aggregators }
+    }
+
+    pub fn headers(&self) -> Vec<String> {
+        self.specs.iter().map(|spec| spec.name.clone()).collect()
+    }
+
+    /// Adds a row and returns the aggregates up to and including it
+    pub fn update(&mut self, record: &StringRecord) -> Vec<String> {
+        for (spec, aggregator) in self.specs.iter().zip(self.aggregators.iter_mut()) {
+            spec.update(aggregator, record);
+        }
+        self.aggregators.iter().map(Aggregator::result).collect()
+    }
+}
+
+#[cfg(test)]
+fn aggregate(function: &str, fields: &[&str]) -> String {
+    let mut aggregator = Aggregator::new(Function::parse(function).unwrap());
+    for field in fields {
+        aggregator.update(field);
+    }
+    aggregator.result()
+}
+
+#[test]
+fn test_aggregators() {
+    let fields = ["3", "1.5", "", "abc", "10"];
+    assert_eq!(aggregate("sum", &fields), "14.5");
+    assert_eq!(aggregate("avg", &fields), "4.833333333333333");
+    assert_eq!(aggregate("min", &fields), "1.5");
+    assert_eq!(aggregate("max", &fields), "10");
+    assert_eq!(aggregate("count", &fields), "4");
+}
+
+#[test]
+fn test_aggregators_without_values() {
+    assert_eq!(aggregate("sum", &[]), "0");
+    assert_eq!(aggregate("avg", &["x"]), "");
+    assert_eq!(aggregate("min", &[""]), "");
+    assert_eq!(aggregate("count", &[""]), "0");
+    assert!(Function::parse("median").is_err());
+}
+
+#[test]
+fn test_deviation_aggregators() {
+    let fields = ["2", "4", "4", "4", "5", "5", "7", "9"];
+    assert_eq!(aggregate("stdp", &fields), "2");
+    assert_eq!(aggregate("var", &fields), "4.571428571428571");
+    assert_eq!(aggregate("stds", &fields), "2.138089935299395");
+
+    assert_eq!(aggregate("stdp", &["3"]), "0");
+    assert_eq!(aggregate("stds", &["3"]), "");
+    assert_eq!(aggregate("var", &[]), "");
+}
+
+#[test]
+fn test_agg_spec_parse_list() {
+    let headers = StringRecord::from(vec!["region", "amount", "qty"]);
+
+    let specs = AggSpec::parse_list("sum:amount,count", &headers, &[]).unwrap();
+    let names: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+    assert_eq!(names, vec!["sum_amount", "count"]);
+    assert_eq!(specs[0].column, Some(1));
+    assert_eq!(specs[1].column, None);
+
+    // Plain functions apply to the default columns
+    let specs = AggSpec::parse_list("max", &headers, &[1, 2]).unwrap();
+    let names: Vec<&str> = specs.iter().map(|spec| spec.name.as_str()).collect();
+    assert_eq!(names, vec!["max_amount", "max_qty"]);
+
+    assert!(AggSpec::parse_list("sum", &headers, &[]).is_err());
+    assert!(AggSpec::parse_list("sum:price", &headers, &[]).is_err());
+}
+
+#[test]
+fn test_group_by() {
+    let headers = StringRecord::from(vec!["region", "amount"]);
+    let specs = AggSpec::parse_list("sum:amount,count", &headers, &[]).unwrap();
+    let mut group_by = GroupBy::new(vec![0], specs);
+    for row in [["eu", "10"], ["us", "5"], ["eu", "2.5"], ["eu", ""]] {
+        group_by.update(&StringRecord::from(row.to_vec()));
+    }
+
+    assert_eq!(group_by.headers(&headers), vec!["region", "sum_amount", "count"]);
+    assert_eq!(group_by.rows(), vec![vec!["eu", "12.5", "3"], vec!["us", "5", "1"]]);
+}
+
+#[test]
+fn test_ungrouped_aggregates() {
+    let headers = StringRecord::from(vec!["amount", "price", "qty"]);
+    let specs = AggSpec::parse_list("sum:amount,avg:price,max:qty", &headers, &[]).unwrap();
+    let mut group_by = GroupBy::new(vec![], specs);
+    assert_eq!(group_by.rows(), vec![vec!["0", "", ""]]);
+
+    for row in [["10", "2", "1"], ["5", "4", "7"]] {
+        group_by.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(group_by.headers(&headers), vec!["sum_amount", "avg_price", "max_qty"]);
+    assert_eq!(group_by.rows(), vec![vec!["15", "3", "7"]]);
+}
+
+#[test]
+fn test_mode_aggregator() {
+    assert_eq!(aggregate("mode", &["rome", "paris", "", "paris", "rome", "paris"]), "paris (3)");
+    // Ties go to the value seen first
+    assert_eq!(aggregate("mode", &["rome", "paris", "paris", "rome"]), "rome (2)");
+    assert_eq!(aggregate("mode", &["", " "]), "");
+}
+
+#[test]
+fn test_running() {
+    let headers = StringRecord::from(vec!["day", "amount"]);
+    let mut running = Running::new(AggSpec::parse_list("sum:amount,count", &headers, &[]).unwrap());
+    assert_eq!(running.headers(), vec!["sum_amount", "count"]);
+    assert_eq!(running.update(&StringRecord::from(vec!["mon", "10"])), vec!["10", "1"]);
+    assert_eq!(running.update(&StringRecord::from(vec!["tue", ""])), vec!["10", "2"]);
+    assert_eq!(running.update(&StringRecord::from(vec!["wed", "2.5"])), vec!["12.5", "3"]);
+}
+
+#[test]
+fn test_count_distinct_aggregators() {
+    let fields = ["a", "b", "a", "", "c", "b"];
+    assert_eq!(aggregate("count_distinct", &fields), "3");
+    assert_eq!(aggregate("approx_count_distinct", &fields), "3");
+
+    let mut hyper_log_log = HyperLogLog::new();
+    for value in 0..100000 {
+        hyper_log_log.insert(&value.to_string());
+    }
+    let estimate = hyper_log_log.estimate() as f64;
+    assert!((estimate - 100000.0).abs() < 5000.0, "{}", estimate);
+}
@@ -0,0 +1,563 @@
+mod expr;
+mod replace;
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use chrono::format::{Item, StrftimeItems};
+use csv::StringRecord;
+use expr::Expr;
+use replace::Substitution;
+use crate::value;
+
+/// Position of a column by its header name
+pub fn column_index(headers: &StringRecord, name: &str, option: &str) -> Result<usize, String> {
+    headers.iter().position(|header| header == name.trim())
+        .ok_or_else(|| format!("Unknown column '{}' in {}", name.trim(), option))
+}
+
+/// Renames the headers with a list like "old=new,old2=new2", for --rename.
+/// The other options refer to the columns by their new names
+pub fn rename(headers: &StringRecord, rename_str: &str) -> Result<StringRecord, String> {
+    let mut names: Vec<String> = headers.iter().map(String::from).collect();
+    for pair in rename_str.split(',') {
+        let (old, new) = pair.split_once('=')
+            .ok_or_else(|| format!("Expected old=new in --rename, got '{}'", pair))?;
+        names[column_index(headers, old, "--rename")?] = String::from(new.trim());
+    }
+    Ok(StringRecord::from(names))
+}
+
+/// Built-in string normalizations of --transform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringFunction {
+    Lower,
+    Upper,
+    Trim,
+    Title,
+    Squish,
+}
+
+impl StringFunction {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "lower" => Ok(StringFunction::Lower),
+            "upper" => Ok(StringFunction::Upper),
+            "trim" => Ok(StringFunction::Trim),
+            "title" => Ok(StringFunction::Title),
+            "squish" => Ok(StringFunction::Squish),
+            _ => Err(format!("Unknown transform '{}', expected one of: lower, upper, trim, title, squish", name.trim())),
+        }
+    }
+
+    pub fn apply(&self, field: &str) -> String {
+        match self {
+            StringFunction::Lower => field.to_lowercase(),
+            StringFunction::Upper => field.to_uppercase(),
+            StringFunction::Trim => String::from(field.trim()),
+            // Capitalizes each word and lowercases the rest
+            StringFunction::Title => {
+                let mut title = String::with_capacity(field.len());
+                let mut word_start = true;
+                for c in field.chars() {
+                    match word_start {
+                        true => title.extend(c.to_uppercase()),
+                        false => title.extend(c.to_lowercase()),
+                    }
+                    word_start = !c.is_alphanumeric();
+                }
+                title
+            },
+            // Trims and collapses the inner runs of whitespace into a space
+            StringFunction::Squish => field.split_whitespace().collect::<Vec<&str>>().join(" "),
+        }
+    }
+}
+
+/// Type of a --cast column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CastType {
+    Int,
+    Float,
+    Bool,
+}
+
+impl CastType {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "int" | "integer" => Ok(CastType::Int),
+            "float" | "double" => Ok(CastType::Float),
+            "bool" | "boolean" => Ok(CastType::Bool),
+            _ => Err(format!("Unknown type '{}' in --cast, expected one of: int, float, bool", name.trim())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CastType::Int => "int",
+            CastType::Float => "float",
+            CastType::Bool => "bool",
+        }
+    }
+
+    /// Normalized value, None when the field isn't of the type
+    pub fn cast(&self, field: &str) -> Option<String> {
+        match self {
+            CastType::Int => value::parse_integer(field).map(|integer| integer.to_string()),
+            CastType::Float => value::parse_number(field).map(|number| number.to_string()),
+            CastType::Bool => match field.trim().to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => Some(String::from("true")),
+                "false" | "f" | "no" | "n" | "0" => Some(String::from("false")),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// What --cast does with a value that isn't of the type, for --cast-error
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum CastError {
+    /// Stop with an error
+    Error,
+    /// Leave the row out
+    Skip,
+    /// Empty the value
+    Blank,
+}
+
+/// Change made to every record as it's read
+enum Step {
+    /// Replaces a field with its normalized value, for --transform
+    Transform(usize, Vec<StringFunction>),
+    /// Parses dates with the first format and writes them with the second
+    /// one, for --date
+    Date(usize, String, String),
+    /// Applies a regex substitution to a column, or to all of them, for
+    /// --replace
+    Replace(Option<usize>, Substitution),
+    /// Appends the parts of a field split on a delimiter, as many as the new
+    /// columns, for --split-col
+    Split(usize, String, usize),
+    /// Appends the concatenation of fields and literals, for --merge-cols
+    Merge(Vec<MergePart>),
+    /// Appends the value of an expression, for --derive
+    Derive(Expr),
+    /// Normalizes the values of a column to a type, for --cast
+    Cast(usize, CastType, CastError),
+    /// Replaces the empty fields of a column, or of all of them, for
+    /// --fill-na and --fill-na-all
+    Fill(Option<usize>, String),
+}
+
+/// Piece of a --merge-cols value
+#[derive(Debug, PartialEq)]
+enum MergePart {
+    Column(usize),
+    Literal(String),
+}
+
+/// Parses the pieces of "street+', '+city", with literals in single or
+/// double quotes so they can have '+' in them
+fn parse_merge_parts(parts_str: &str, headers: &StringRecord) -> Result<Vec<MergePart>, String> {
+    let mut parts = Vec::new();
+    let mut chars = parts_str.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek().copied() {
+            Some(quote @ ('\'' | '"')) => {
+                chars.next();
+                let mut literal = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => literal.extend(chars.next()),
+                        Some(c) if c == quote => break,
+                        Some(c) => literal.push(c),
+                        None => return Err(format!("Unclosed quote in --merge-cols '{}'", parts_str)),
+                    }
+                }
+                parts.push(MergePart::Literal(literal));
+            },
+            _ => {
+                let name: String = std::iter::from_fn(|| chars.next_if(|&c| c != '+')).collect();
+                if name.trim().is_empty() {
+                    return Err(format!("Expected a column or a quoted text in --merge-cols '{}'", parts_str));
+                }
+                parts.push(MergePart::Column(column_index(headers, &name, "--merge-cols")?));
+            },
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            Some('+') => continue,
+            None => return Ok(parts),
+            Some(c) => return Err(format!("Unexpected '{}' in --merge-cols '{}'", c, parts_str)),
+        }
+    }
+}
+
+/// Checks a chrono format, since formatting with a wrong one panics
+fn check_date_format(format: &str) -> Result<(), String> {
+    match StrftimeItems::new(format).any(|item| item == Item::Error) {
+        true => Err(format!("Invalid date format '{}' in --date", format)),
+        false => Ok(()),
+    }
+}
+
+/// Record with one of its fields replaced
+pub fn with_field(record: &StringRecord, column: usize, value: &str) -> StringRecord {
+    record.iter().enumerate().map(|(i, field)| if i == column { value } else { field }).collect()
+}
+
+/// Changes made to the records before the filters, so the other options
+/// can use their result. The steps adding columns also add their headers
+#[derive(Default)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+impl Pipeline {
+    /// Adds --transform normalizations like "name=lower,code=trim|upper".
+    /// Several functions for a column are applied from left to right
+    pub fn transform(&mut self, headers: &StringRecord, transform_str: &str) -> Result<(), String> {
+        for pair in transform_str.split(',') {
+            let (name, functions) = pair.split_once('=')
+                .ok_or_else(|| format!("Expected column=function in --transform, got '{}'", pair))?;
+            let functions = functions.split('|').map(StringFunction::parse).collect::<Result<_, _>>()?;
+            self.steps.push(Step::Transform(column_index(headers, name, "--transform")?, functions));
+        }
+        Ok(())
+    }
+
+    /// Adds a --date reformatting like "created_at:%d/%m/%Y->%Y-%m-%d".
+    /// Dates in the ISO formats are also read, the other values are kept as
+    /// they are, so several --date can handle a column with mixed formats
+    pub fn date(&mut self, headers: &StringRecord, date_str: &str) -> Result<(), String> {
+        let (name, formats) = date_str.split_once(':')
+            .ok_or_else(|| format!("Expected column:input->output in --date, got '{}'", date_str))?;
+        let (input_format, output_format) = formats.split_once("->")
+            .ok_or_else(|| format!("Expected column:input->output in --date, got '{}'", date_str))?;
+        check_date_format(input_format)?;
+        check_date_format(output_format)?;
+        let column = column_index(headers, name, "--date")?;
+        self.steps.push(Step::Date(column, String::from(input_format), String::from(output_format)));
+        Ok(())
+    }
+
+    /// Adds a --replace substitution like "col:s/pattern/replacement/g", for
+    /// every column when there's no "col:". A prefix naming a column wins
+    /// over a pattern with ':' in it
+    pub fn replace(&mut self, headers: &StringRecord, replace_str: &str) -> Result<(), String> {
+        let all_columns = replace_str.starts_with('s')
+            && replace_str[1..].starts_with(|c: char| !c.is_alphanumeric() && c != ':');
+        let (column, sed_str) = match replace_str.split_once(':') {
+            Some((name, sed_str)) => match column_index(headers, name, "--replace") {
+                Ok(column) => (Some(column), sed_str),
+                Err(_) if all_columns => (None, replace_str),
+                Err(err) => return Err(err),
+            },
+            None => (None, replace_str),
+        };
+        self.steps.push(Step::Replace(column, Substitution::parse(sed_str.trim_start())?));
+        Ok(())
+    }
+
+    /// Adds the columns of a --split-col like "full_name: :first,last". The
+    /// last column gets the rest of the field, and the missing parts are empty
+    pub fn split(&mut self, headers: &mut StringRecord, split_str: &str) -> Result<(), String> {
+        let (name, rest) = split_str.split_once(':')
+            .ok_or_else(|| format!("Expected column:delimiter:names in --split-col, got '{}'", split_str))?;
+        let (delimiter, names) = rest.rsplit_once(':')
+            .ok_or_else(|| format!("Expected column:delimiter:names in --split-col, got '{}'", split_str))?;
+        if delimiter.is_empty() {
+            return Err(format!("Empty delimiter in --split-col '{}'", split_str));
+        }
+        let column = column_index(headers, name, "--split-col")?;
+        let names: Vec<&str> = names.split(',').map(str::trim).collect();
+        self.steps.push(Step::Split(column, String::from(delimiter), names.len()));
+        for name in names {
+            headers.push_field(name);
+        }
+        Ok(())
+    }
+
+    /// Adds a --merge-cols column like "address=street+', '+city"
+    pub fn merge(&mut self, headers: &mut StringRecord, merge_str: &str) -> Result<(), String> {
+        let (name, parts_str) = merge_str.split_once('=')
+            .ok_or_else(|| format!("Expected name=parts in --merge-cols, got '{}'", merge_str))?;
+        self.steps.push(Step::Merge(parse_merge_parts(parts_str, headers)?));
+        headers.push_field(name.trim());
+        Ok(())
+    }
+
+    /// Adds a --derive column like "total=price*qty". The expression can
+    /// use the columns derived before it
+    pub fn derive(&mut self, headers: &mut StringRecord, derive_str: &str) -> Result<(), String> {
+        let (name, expr_str) = derive_str.split_once('=')
+            .ok_or_else(|| format!("Expected name=expression in --derive, got '{}'", derive_str))?;
+        self.steps.push(Step::Derive(Expr::parse(expr_str, headers)?));
+        headers.push_field(name.trim());
+        Ok(())
+    }
+
+    /// Adds a --fill-na default like "country=unknown"
+    pub fn fill_na(&mut self, headers: &StringRecord, fill_str: &str) -> Result<(), String> {
+        let (name, default) = fill_str.split_once('=')
+            .ok_or_else(|| format!("Expected column=default in --fill-na, got '{}'", fill_str))?;
+        self.steps.push(Step::Fill(Some(column_index(headers, name, "--fill-na")?), String::from(default)));
+        Ok(())
+    }
+
+    /// Adds the --fill-na-all default, for the empty fields of every column
+    pub fn fill_na_all(&mut self, default: &str) {
+        self.steps.push(Step::Fill(None, String::from(default)));
+    }
+
+    /// Adds the --cast types like "amount:float,id:int,flag:bool". Empty
+    /// values are left empty
+    pub fn cast(&mut self, headers: &StringRecord, cast_str: &str, on_error: CastError) -> Result<(), String> {
+        for pair in cast_str.split(',') {
+            let (name, type_name) = pair.split_once(':')
+                .ok_or_else(|| format!("Expected column:type in --cast, got '{}'", pair))?;
+            self.steps.push(Step::Cast(column_index(headers, name, "--cast")?, CastType::parse(type_name)?, on_error));
+        }
+        Ok(())
+    }
+
+    /// Whether some records may be left out, so the rows can't be counted
+    /// before the pipeline
+    pub fn skips_rows(&self) -> bool {
+        self.steps.iter().any(|step| matches!(step, Step::Cast(_, _, CastError::Skip)))
+    }
+
+    /// The changed record, None when it's left out by --cast
+    pub fn apply(&self, mut record: StringRecord) -> Result<Option<StringRecord>, String> {
+        for step in &self.steps {
+            match step {
+                Step::Transform(column, functions) => {
+                    let Some(field) = record.get(*column) else { continue };
+                    let value = functions.iter().fold(String::from(field), |value, function| function.apply(&value));
+                    record = with_field(&record, *column, &value);
+                },
+                Step::Date(column, input_format, output_format) => {
+                    let Some(field) = record.get(*column) else { continue };
+                    let Some(date) = value::parse_date(field, Some(input_format.as_str()).filter(|format| !format.is_empty())) else { continue };
+                    let mut value = String::new();
+                    if write!(value, "{}", date.format(output_format)).is_ok() {
+                        record = with_field(&record, *column, &value);
+                    }
+                },
+                Step::Replace(Some(column), substitution) => {
+                    let Some(field) = record.get(*column) else { continue };
+                    if let Cow::Owned(value) = substitution.apply(field) {
+                        record = with_field(&record, *column, &value);
+                    }
+                },
+                Step::Replace(None, substitution) => {
+                    record = record.iter().map(|field| substitution.apply(field).into_owned()).collect();
+                },
+                Step::Split(column, delimiter, parts) => {
+                    let field = String::from(record.get(*column).unwrap_or_default());
+                    let mut split = field.splitn(*parts, delimiter.as_str());
+                    for _ in 0..*parts {
+                        record.push_field(split.next().unwrap_or_default());
+                    }
+                },
+                Step::Merge(parts) => {
+                    let value: String = parts.iter().map(|part| match part {
+                        MergePart::Column(i) => record.get(*i).unwrap_or_default(),
+                        MergePart::Literal(literal) => literal,
+                    }).collect();
+                    record.push_field(&value);
+                },
+                Step::Derive(expr) => {
+                    let value = expr.evaluate(&record).map(|number| number.to_string()).unwrap_or_default();
+                    record.push_field(&value);
+                },
+                Step::Cast(column, cast_type, on_error) => {
+                    let field = record.get(*column).unwrap_or_default();
+                    if field.is_empty() {
+                        continue;
+                    }
+                    let value = match (cast_type.cast(field), on_error) {
+                        (Some(value), _) => value,
+                        (None, CastError::Error) => return Err(format!("Can't cast '{}' to {}", field, cast_type.name())),
+                        (None, CastError::Skip) => return Ok(None),
+                        (None, CastError::Blank) => String::new(),
+                    };
+                    if value != field {
+                        record = with_field(&record, *column, &value);
+                    }
+                },
+                Step::Fill(Some(column), default) => {
+                    if record.get(*column) == Some("") {
+                        record = with_field(&record, *column, default);
+                    }
+                },
+                Step::Fill(None, default) => {
+                    if record.iter().any(str::is_empty) {
+                        record = record.iter().map(|field| if field.is_empty() { default } else { field }).collect();
+                    }
+                },
+            }
+        }
+        Ok(Some(record))
+    }
+}
+
+#[test]
+fn test_rename() {
+    let headers = StringRecord::from(vec!["id", "nm", "qty"]);
+    assert_eq!(rename(&headers, "nm=name, qty=quantity"), Ok(StringRecord::from(vec!["id", "name", "quantity"])));
+    // The names refer to the original headers, so they can be swapped
+    assert_eq!(rename(&headers, "id=nm,nm=id"), Ok(StringRecord::from(vec!["nm", "id", "qty"])));
+    assert!(rename(&headers, "price=cost").is_err());
+    assert!(rename(&headers, "id").is_err());
+}
+
+#[test]
+fn test_pipeline_derive() {
+    let mut headers = StringRecord::from(vec!["price", "qty"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.derive(&mut headers, "total=price*qty").unwrap();
+    pipeline.derive(&mut headers, "with_tax = total * 1.5").unwrap();
+    assert_eq!(headers, vec!["price", "qty", "total", "with_tax"]);
+    assert!(pipeline.derive(&mut headers, "price*2").is_err());
+
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["2", "3"])).unwrap().unwrap(), vec!["2", "3", "6", "9"]);
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["2", ""])).unwrap().unwrap(), vec!["2", "", "", ""]);
+}
+
+#[test]
+fn test_string_functions() {
+    assert_eq!(StringFunction::Lower.apply("ÁBC d"), "ábc d");
+    assert_eq!(StringFunction::Upper.apply("straße"), "STRASSE");
+    assert_eq!(StringFunction::Trim.apply("  x y "), "x y");
+    assert_eq!(StringFunction::Title.apply("new YORK-city o'neil"), "New York-City O'Neil");
+    assert_eq!(StringFunction::Squish.apply("  a   b\tc "), "a b c");
+    assert!(StringFunction::parse("reverse").is_err());
+
+    let headers = StringRecord::from(vec!["name", "code"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.transform(&headers, "name=squish|title, code=upper").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["  ada   LOVELACE", "ab1"])).unwrap().unwrap(), vec!["Ada Lovelace", "AB1"]);
+    assert!(pipeline.transform(&headers, "city=upper").is_err());
+}
+
+#[test]
+fn test_pipeline_date() {
+    let headers = StringRecord::from(vec!["id", "created_at"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.date(&headers, "created_at:%d/%m/%Y->%Y-%m-%d").unwrap();
+    pipeline.date(&headers, "created_at:%m-%d-%Y %H:%M->%Y-%m-%d").unwrap();
+    let dates = |field: &str| pipeline.apply(StringRecord::from(vec!["1", field])).unwrap().unwrap()[1].to_string();
+    assert_eq!(dates("31/12/2024"), "2024-12-31");
+    assert_eq!(dates("12-31-2024 10:30"), "2024-12-31");
+    assert_eq!(dates("2024-12-31T10:30:00"), "2024-12-31");
+    assert_eq!(dates("soon"), "soon");
+
+    let mut pipeline = Pipeline::default();
+    pipeline.date(&headers, "created_at:->%d %b %Y %H:%M").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["1", "2024-03-05"])).unwrap().unwrap()[1], *"05 Mar 2024 00:00");
+
+    assert!(pipeline.date(&headers, "created_at:%d/%m/%Y").is_err());
+    assert!(pipeline.date(&headers, "created_at:%d/%m/%Y->%Q").is_err());
+    assert!(pipeline.date(&headers, "updated_at:%d/%m/%Y->%Y-%m-%d").is_err());
+}
+
+#[test]
+fn test_pipeline_split() {
+    let mut headers = StringRecord::from(vec!["full_name", "tags"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.split(&mut headers, "full_name: :first,last").unwrap();
+    pipeline.split(&mut headers, "tags:::one").unwrap();
+    assert_eq!(headers, vec!["full_name", "tags", "first", "last", "one"]);
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["Ada King Lovelace", "a::b"])).unwrap().unwrap(),
+               vec!["Ada King Lovelace", "a::b", "Ada", "King Lovelace", "a::b"]);
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["Plato", ""])).unwrap().unwrap(), vec!["Plato", "", "Plato", "", ""]);
+
+    assert!(pipeline.split(&mut headers, "full_name::first").is_err());
+    assert!(pipeline.split(&mut headers, "full_name first,last").is_err());
+    assert!(pipeline.split(&mut headers, "name: :first,last").is_err());
+}
+
+#[test]
+fn test_pipeline_merge() {
+    let mut headers = StringRecord::from(vec!["street", "city", "zip code"]);
+    assert_eq!(parse_merge_parts("street + ' + ' + city", &headers),
+               Ok(vec![MergePart::Column(0), MergePart::Literal(String::from(" + ")), MergePart::Column(1)]));
+    assert!(parse_merge_parts("street+'", &headers).is_err());
+    assert!(parse_merge_parts("street++city", &headers).is_err());
+    assert!(parse_merge_parts("street 'x'", &headers).is_err());
+    assert!(parse_merge_parts("country", &headers).is_err());
+
+    let mut pipeline = Pipeline::default();
+    pipeline.merge(&mut headers, "address=street+', '+city+\" (\"+zip code+\")\"").unwrap();
+    assert_eq!(headers, vec!["street", "city", "zip code", "address"]);
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["1 Main St", "Springfield", "12345"])).unwrap().unwrap()[3],
+               *"1 Main St, Springfield (12345)");
+}
+
+#[test]
+fn test_pipeline_fill_na() {
+    let headers = StringRecord::from(vec!["id", "country", "notes"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.fill_na(&headers, "country=unknown").unwrap();
+    assert!(pipeline.fill_na(&headers, "city=unknown").is_err());
+    assert!(pipeline.fill_na(&headers, "country").is_err());
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["1", "", ""])).unwrap().unwrap(), vec!["1", "unknown", ""]);
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["2", "AR", " "])).unwrap().unwrap(), vec!["2", "AR", " "]);
+
+    pipeline.fill_na_all("-");
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["", "", ""])).unwrap().unwrap(), vec!["-", "unknown", "-"]);
+}
+
+#[test]
+fn test_pipeline_replace() {
+    let headers = StringRecord::from(vec!["phone", "s"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.replace(&headers, r"phone:s/[^0-9]//g").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["(555) 010-99", "a-b"])).unwrap().unwrap(), vec!["55501099", "a-b"]);
+    pipeline.replace(&headers, "s/-/+/").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["5-5", "a-b-c"])).unwrap().unwrap(), vec!["55", "a+b-c"]);
+
+    let mut pipeline = Pipeline::default();
+    pipeline.replace(&headers, "s:s/a/b/").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["a", "a"])).unwrap().unwrap(), vec!["a", "b"]);
+    assert!(pipeline.replace(&headers, "email:s/a/b/").is_err());
+    assert!(pipeline.replace(&headers, "phone:s/a/b").is_err());
+
+    // Columns looking like the start of a substitution, and a pattern with ':'
+    let headers = StringRecord::from(vec!["s-code", "time"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.replace(&headers, "s-code:s/a/Z/").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["aa", "aa"])).unwrap().unwrap(), vec!["Za", "aa"]);
+    let mut pipeline = Pipeline::default();
+    pipeline.replace(&headers, "s/:/h/").unwrap();
+    assert_eq!(pipeline.apply(StringRecord::from(vec!["a:b", "10:30"])).unwrap().unwrap(), vec!["ahb", "10h30"]);
+}
+
+#[test]
+fn test_pipeline_cast() {
+    assert_eq!(CastType::Int.cast(" 42 "), Some(String::from("42")));
+    assert_eq!(CastType::Int.cast("4.2"), None);
+    assert_eq!(CastType::Float.cast("1.50"), Some(String::from("1.5")));
+    assert_eq!(CastType::Bool.cast("Yes"), Some(String::from("true")));
+    assert_eq!(CastType::Bool.cast("0"), Some(String::from("false")));
+    assert_eq!(CastType::Bool.cast("maybe"), None);
+
+    let headers = StringRecord::from(vec!["id", "amount", "flag"]);
+    let cast = |on_error: CastError, record: Vec<&str>| {
+        let mut pipeline = Pipeline::default();
+        pipeline.cast(&headers, "id:int, amount:float,flag:bool", on_error).unwrap();
+        pipeline.apply(StringRecord::from(record))
+    };
+    assert_eq!(cast(CastError::Error, vec!["007", "", "N"]), Ok(Some(StringRecord::from(vec!["7", "", "false"]))));
+    assert!(cast(CastError::Error, vec!["1", "lots", "y"]).is_err());
+    assert_eq!(cast(CastError::Skip, vec!["1", "lots", "y"]), Ok(None));
+    assert_eq!(cast(CastError::Blank, vec!["1", "lots", "y"]), Ok(Some(StringRecord::from(vec!["1", "", "true"]))));
+
+    let mut pipeline = Pipeline::default();
+    assert!(pipeline.cast(&headers, "id:date", CastError::Error).is_err());
+    assert!(pipeline.cast(&headers, "price:float", CastError::Error).is_err());
+    assert!(!pipeline.skips_rows());
+    pipeline.cast(&headers, "id:int", CastError::Skip).unwrap();
+    assert!(pipeline.skips_rows());
+}
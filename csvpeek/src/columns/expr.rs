@@ -0,0 +1,203 @@
+// Parser and evaluator of the --derive expressions, arithmetic over the
+// fields of a row.
+//
+// expr   := term (('+' | '-') term)*
+// term   := factor (('*' | '/' | '%') factor)*
+// factor := '-' factor | '(' expr ')' | number | column
+//
+// Columns are named by their header, quoted with " or ' when the name has
+// spaces or operator characters. A field that is not a number makes the
+// whole result empty, like a division by zero does.
+
+use csv::StringRecord;
+use crate::value;
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Column(usize),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, BinaryOperator, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BinaryOperator {
+    Add,        // +
+    Subtract,   // -
+    Multiply,   // *
+    Divide,     // /
+    Remainder,  // %
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+const SYMBOLS: [char; 7] = ['+', '-', '*', '/', '%', '(', ')'];
+
+impl Expr {
+    pub fn parse(expr_str: &str, headers: &StringRecord) -> Result<Self, String> {
+        let tokens = tokenize(expr_str)?;
+        let mut parser = Parser { tokens: &tokens, position: 0, headers };
+        let expr = parser.expr()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} in expression '{}'", token, expr_str)),
+        }
+    }
+
+    /// Value of the expression for a row, None when a field is not a
+    /// number or the result is not finite
+    pub fn evaluate(&self, record: &StringRecord) -> Option<f64> {
+        let result = match self {
+            Expr::Number(number) => *number,
+            Expr::Column(i) => value::parse_number(record.get(*i)?)?,
+            Expr::Negate(expr) => -expr.evaluate(record)?,
+            Expr::Binary(left, operator, right) => {
+                let (left, right) = (left.evaluate(record)?, right.evaluate(record)?);
+                match operator {
+                    BinaryOperator::Add => left + right,
+                    BinaryOperator::Subtract => left - right,
+                    BinaryOperator::Multiply => left * right,
+                    BinaryOperator::Divide => left / right,
+                    BinaryOperator::Remainder => left % right,
+                }
+            },
+        };
+        Some(result).filter(|result| result.is_finite())
+    }
+}
+
+fn tokenize(expr_str: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expr_str.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if SYMBOLS.contains(&c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => name.extend(chars.next()),
+                    Some(next) if next == c => break,
+                    Some(next) => name.push(next),
+                    None => return Err(format!("Unclosed quote in expression '{}'", expr_str)),
+                }
+            }
+            tokens.push(Token::Name(name));
+        } else {
+            let mut word = String::new();
+            while let Some(&next) = chars.peek() {
+                // Exponents like 1e-3 keep their sign
+                let exponent_sign = (next == '-' || next == '+') && word.ends_with(['e', 'E'])
+                    && word[..word.len() - 1].parse::<f64>().is_ok();
+                if next.is_whitespace() || (SYMBOLS.contains(&next) && !exponent_sign) || next == '"' || next == '\'' {
+                    break;
+                }
+                word.push(next);
+                chars.next();
+            }
+            tokens.push(match word.parse::<f64>() {
+                Ok(number) if number.is_finite() => Token::Number(number),
+                _ => Token::Name(word),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    headers: &'a StringRecord,
+}
+
+impl Parser<'_> {
+    fn next_symbol(&mut self, symbols: &[char]) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(symbol)) if symbols.contains(symbol) => {
+                self.position += 1;
+                Some(*symbol)
+            },
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        while let Some(symbol) = self.next_symbol(&['+', '-']) {
+            let operator = if symbol == '+' { BinaryOperator::Add } else { BinaryOperator::Subtract };
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.term()?));
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut expr = self.factor()?;
+        while let Some(symbol) = self.next_symbol(&['*', '/', '%']) {
+            let operator = match symbol {
+                '*' => BinaryOperator::Multiply,
+                '/' => BinaryOperator::Divide,
+                _ => BinaryOperator::Remainder,
+            };
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(self.factor()?));
+        }
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
+        if self.next_symbol(&['-']).is_some() {
+            return Ok(Expr::Negate(Box::new(self.factor()?)));
+        }
+        if self.next_symbol(&['(']).is_some() {
+            let expr = self.expr()?;
+            return match self.next_symbol(&[')']) {
+                Some(_) => Ok(expr),
+                None => Err(String::from("Missing ')' in expression")),
+            };
+        }
+        let token = self.tokens.get(self.position).ok_or("Expression ended where a value was expected")?;
+        self.position += 1;
+        match token {
+            Token::Number(number) => Ok(Expr::Number(*number)),
+            Token::Name(name) => self.headers.iter().position(|header| header == name)
+                .map(Expr::Column)
+                .ok_or_else(|| format!("Unknown column '{}' in expression", name)),
+            Token::Symbol(symbol) => Err(format!("Unexpected '{}' in expression", symbol)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_expr() {
+    let headers = StringRecord::from(vec!["price", "qty", "unit cost"]);
+    assert_eq!(Expr::parse("price*qty", &headers), Ok(Expr::Binary(
+        Box::new(Expr::Column(0)), BinaryOperator::Multiply, Box::new(Expr::Column(1)),
+    )));
+    assert_eq!(Expr::parse("-1e-3", &headers), Ok(Expr::Negate(Box::new(Expr::Number(0.001)))));
+    assert!(Expr::parse("price*", &headers).is_err());
+    assert!(Expr::parse("(price", &headers).is_err());
+    assert!(Expr::parse("price qty", &headers).is_err());
+    assert!(Expr::parse("weight*2", &headers).is_err());
+}
+
+#[test]
+fn test_evaluate_expr() {
+    let headers = StringRecord::from(vec!["price", "qty", "unit cost", "note"]);
+    let record = StringRecord::from(vec!["2.5", "4", "1", "n/a"]);
+    let evaluate = |expr_str: &str| Expr::parse(expr_str, &headers).unwrap().evaluate(&record);
+    assert_eq!(evaluate("price*qty"), Some(10.0));
+    assert_eq!(evaluate("price + qty * 2"), Some(10.5));
+    assert_eq!(evaluate("(price + qty) * 2"), Some(13.0));
+    assert_eq!(evaluate("qty - \"unit cost\" - 1"), Some(2.0));
+    assert_eq!(evaluate("qty % 3 - -price"), Some(3.5));
+    assert_eq!(evaluate("qty / 0"), None);
+    assert_eq!(evaluate("note * 2"), None);
+}
@@ -0,0 +1,102 @@
+// sed-style substitutions of --replace, like "s/pattern/replacement/g".
+//
+// The character after the 's' delimits the parts, and is escaped with a
+// backslash inside them. The replacement refers to the groups sed-style,
+// with \1 to \9 and & for the whole match. The flags are g, to replace every
+// match instead of the first one, and i, to ignore case.
+
+use std::borrow::Cow;
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,  // in the syntax of the regex crate
+    global: bool,
+}
+
+impl Substitution {
+    pub fn parse(sed_str: &str) -> Result<Self, String> {
+        let mut chars = sed_str.chars();
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) if !delimiter.is_alphanumeric() && delimiter != '\\' => delimiter,
+            _ => return Err(format!("Expected s/pattern/replacement/ in --replace, got '{}'", sed_str)),
+        };
+        let mut parts = vec![String::new()];
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                    Some(next) => parts.last_mut().unwrap().extend(['\\', next]),
+                    None => parts.last_mut().unwrap().push('\\'),
+                },
+                c if c == delimiter && parts.len() < 3 => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+        let [pattern, replacement, flags] = &parts[..] else {
+            return Err(format!("Expected s/pattern/replacement/ in --replace, got '{}'", sed_str));
+        };
+        if let Some(flag) = flags.chars().find(|&flag| flag != 'g' && flag != 'i') {
+            return Err(format!("Unknown flag '{}' in --replace '{}', expected g or i", flag, sed_str));
+        }
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(flags.contains('i'))
+            .build()
+            .map_err(|err| format!("Invalid regex '{}' in --replace: {}", pattern, err))?;
+        Ok(Self { regex, replacement: regex_replacement(replacement), global: flags.contains('g') })
+    }
+
+    pub fn apply<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        match self.global {
+            true => self.regex.replace_all(field, self.replacement.as_str()),
+            false => self.regex.replace(field, self.replacement.as_str()),
+        }
+    }
+}
+
+/// Translates a sed replacement to the syntax of the regex crate, which
+/// names the groups with $
+fn regex_replacement(replacement: &str) -> String {
+    let mut translated = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(group @ '0'..='9') => translated.push_str(&format!("${{{}}}", group)),
+                Some('n') => translated.push('\n'),
+                Some('t') => translated.push('\t'),
+                Some('$') => translated.push_str("$$"),
+                Some(next) => translated.push(next),
+                None => translated.push('\\'),
+            },
+            '&' => translated.push_str("${0}"),
+            '$' => translated.push_str("$$"),
+            c => translated.push(c),
+        }
+    }
+    translated
+}
+
+#[test]
+fn test_parse_substitution() {
+    assert!(Substitution::parse("s/a/b/").is_ok());
+    assert!(Substitution::parse("s|a/b|c|g").is_ok());
+    assert!(Substitution::parse("s/a/b").is_err());
+    assert!(Substitution::parse("y/a/b/").is_err());
+    assert!(Substitution::parse("s/a/b/x").is_err());
+    assert!(Substitution::parse("s/(a/b/").is_err());
+    assert_eq!(regex_replacement(r"\2-\1 & \& $5"), "${2}-${1} ${0} & $$5");
+}
+
+#[test]
+fn test_apply_substitution() {
+    let replace = |sed_str: &str, field: &str| Substitution::parse(sed_str).unwrap().apply(field).into_owned();
+    assert_eq!(replace("s/o/0/", "foo"), "f0o");
+    assert_eq!(replace("s/o/0/g", "foo"), "f00");
+    assert_eq!(replace("s/O/0/gi", "foo"), "f00");
+    assert_eq!(replace(r"s/(\w+)@(\w+)/\2 at \1/", "ada@example"), "example at ada");
+    assert_eq!(replace(r"s/\//-/g", "2024/01/05"), "2024-01-05");
+    assert_eq!(replace("s/[0-9]+/<&>/", "id 42"), "id <42>");
+    assert_eq!(replace("s/x/$1/", "x"), "$1");
+}
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::io::{self, Write};
+use csv::StringRecord;
+use crate::columns::column_index;
+use crate::input::{self, Input, ReaderOptions};
+use crate::value::ColumnType;
+
+/// Rows of a diff between two files
+#[derive(Debug, Default, PartialEq)]
+pub struct Counts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,  // only for keyed diffs
+    pub unchanged: usize,
+}
+
+/// Position in the new file of each column of the old one, so the columns
+/// can be in another order. Without headers they are matched by position
+pub fn column_order(old: &StringRecord, new: &StringRecord, has_headers: bool) -> Result<Vec<usize>, String> {
+    if !has_headers {
+        return match old.len() == new.len() {
+            true => Ok((0..old.len()).collect()),
+            false => Err(format!("The files have a different number of columns: {} and {}", old.len(), new.len())),
+        };
+    }
+    let order: Option<Vec<usize>> = old.iter().map(|header| new.iter().position(|new_header| new_header == header)).collect();
+    match order {
+        Some(order) if old.len() == new.len() => Ok(order),
+        _ => Err(format!("The files have different columns: {:?} and {:?}", old.iter().collect::<Vec<_>>(),
+                         new.iter().collect::<Vec<_>>())),
+    }
+}
+
+/// Compares the rows of two files as multisets of whole rows: a row repeated
+/// in the new file is only unchanged as many times as it's in the old one.
+/// The added rows are passed to on_row as they are read, with '+', and then
+/// the removed ones in the order of the old file, with '-'
+pub fn diff_rows<F>(old: Input, new: Input, order: &[usize], mut on_row: F) -> Result<Counts, Box<dyn Error>>
+where F: FnMut(char, &StringRecord) -> Result<(), Box<dyn Error>> {
+    // Positions of the old rows not matched yet, by their fields
+    let mut old_rows: HashMap<Vec<String>, VecDeque<usize>> = HashMap::new();
+    for (i, record) in old.records.enumerate() {
+        old_rows.entry(record?.iter().map(String::from).collect()).or_default().push_back(i);
+    }
+    let mut counts = Counts::default();
+    for record in new.records {
+        let record = record?;
+        let row: Vec<String> = order.iter().map(|&i| String::from(record.get(i).unwrap_or_default())).collect();
+        match old_rows.get_mut(&row).and_then(VecDeque::pop_front) {
+            Some(_) => counts.unchanged += 1,
+            None => {
+                counts.added += 1;
+                on_row('+', &StringRecord::from(row))?;
+            },
+        }
+    }
+    let mut removed: Vec<(usize, Vec<String>)> = old_rows.into_iter()
+        .flat_map(|(row, positions)| positions.into_iter().map(move |i| (i, row.clone())))
+        .collect();
+    removed.sort_by_key(|&(i, _)| i);
+    counts.removed = removed.len();
+    for (_, row) in removed {
+        on_row('-', &StringRecord::from(row))?;
+    }
+    Ok(counts)
+}
+
+/// Compares the rows with the same key of two files, describing each change
+/// to on_change like "id=42: price 10.0 -> 12.5", as the new file is read.
+/// The keys only in the new file are "added", and then the ones only in the
+/// old file "removed", in its order. The keys must be unique in each file
+pub fn diff_keyed<F>(old: Input, new: Input, order: &[usize], key_columns: &[usize],
+                     mut on_change: F) -> Result<Counts, Box<dyn Error>>
+where F: FnMut(&str) -> Result<(), Box<dyn Error>> {
+    let headers = old.headers.clone();
+    let key = |row: &StringRecord| key_columns.iter()
+        .map(|&column| format!("{}={}", &headers[column], row.get(column).unwrap_or_default()))
+        .collect::<Vec<String>>()
+        .join(",");
+    let mut old_rows: HashMap<String, (usize, StringRecord)> = HashMap::new();
+    for (i, record) in old.records.enumerate() {
+        let record = record?;
+        if old_rows.insert(key(&record), (i, record.clone())).is_some() {
+            return Err(format!("Duplicate key {} in the old file", key(&record)).into());
+        }
+    }
+    let mut counts = Counts::default();
+    let mut new_keys = HashSet::new();
+    for record in new.records {
+        let record = record?;
+        let row: StringRecord = order.iter().map(|&i| record.get(i).unwrap_or_default()).collect();
+        let row_key = key(&row);
+        if !new_keys.insert(row_key.clone()) {
+            return Err(format!("Duplicate key {} in the new file", row_key).into());
+        }
+        let Some((_, old_row)) = old_rows.remove(&row_key) else {
+            counts.added += 1;
+            on_change(&format!("{}: added", row_key))?;
+            continue;
+        };
+        let changes: Vec<String> = old_row.iter().zip(row.iter()).enumerate()
+            .filter(|(_, (old_field, new_field))| old_field != new_field)
+            .map(|(column, (old_field, new_field))| format!("{} {} -> {}", &headers[column], old_field, new_field))
+            .collect();
+        match changes.is_empty() {
+            true => counts.unchanged += 1,
+            false => {
+                counts.changed += 1;
+                on_change(&format!("{}: {}", row_key, changes.join(", ")))?;
+            },
+        }
+    }
+    let mut removed: Vec<(usize, String)> = old_rows.into_iter().map(|(row_key, (i, _))| (i, row_key)).collect();
+    removed.sort_by_key(|&(i, _)| i);
+    counts.removed = removed.len();
+    for (_, row_key) in removed {
+        on_change(&format!("{}: removed", row_key))?;
+    }
+    Ok(counts)
+}
+
+/// The diff command. With rows, the added and removed rows are printed as a
+/// CSV with a leading diff column and the counts go to stderr. With key
+/// columns, the changes of each key are printed before the counts
+pub fn run(old_path: &str, new_path: &str, rows: bool, key_str: Option<&str>,
+           options: &ReaderOptions) -> Result<(), Box<dyn Error>> {
+    let old = input::open_input(Some(old_path), options)?;
+    let new = input::open_input(Some(new_path), options)?;
+    let order = column_order(&old.headers, &new.headers, options.has_headers)?;
+    if let Some(key_str) = key_str {
+        let key_columns = key_str.split(',')
+            .map(|name| column_index(&old.headers, name, "--key"))
+            .collect::<Result<Vec<usize>, String>>()?;
+        let mut out = io::stdout().lock();
+        let counts = diff_keyed(old, new, &order, &key_columns, |change| Ok(writeln!(out, "{}", change)?))?;
+        println!("Added rows: {}\nRemoved rows: {}\nChanged rows: {}\nUnchanged rows: {}",
+                 counts.added, counts.removed, counts.changed, counts.unchanged);
+        return Ok(());
+    }
+    let mut writer = csv::Writer::from_writer(io::stdout().lock());
+    if rows && options.has_headers {
+        writer.write_record(std::iter::once("diff").chain(old.headers.iter()))?;
+    }
+    let counts = diff_rows(old, new, &order, |sign, row| {
+        if rows {
+            writer.write_record(std::iter::once(sign.to_string().as_str()).chain(row.iter()))?;
+        }
+        Ok(())
+    })?;
+    writer.flush()?;
+    let report = format!("Added rows: {}\nRemoved rows: {}\nUnchanged rows: {}", counts.added, counts.removed, counts.unchanged);
+    match rows {
+        true => eprintln!("{}", report),
+        false => println!("{}", report),
+    }
+    Ok(())
+}
+
+/// Column names of a file with the types inferred from its first rows.
+/// Without headers the columns are named by their position: c1, c2...
+pub fn schema(input: Input, has_headers: bool, infer_rows: usize) -> Result<Vec<(String, ColumnType)>, Box<dyn Error>> {
+    let mut columns: Vec<Vec<String>> = vec![Vec::new(); input.headers.len()];
+    for record in input.records.take(infer_rows) {
+        for (column, field) in columns.iter_mut().zip(record?.iter()) {
+            column.push(String::from(field));
+        }
+    }
+    Ok(columns.iter().enumerate()
+        .map(|(i, fields)| {
+            let name = match has_headers {
+                true => String::from(&input.headers[i]),
+                false => format!("c{}", i + 1),
+            };
+            (name, ColumnType::infer(fields.iter().map(String::as_str)))
+        })
+        .collect())
+}
+
+/// Differences between the schemas of two files, like "added: email (text)",
+/// "removed: phone (int)", "moved: name 2 -> 3" or "type: price int -> float".
+/// A column is moved when its position among the columns of both files changes
+pub fn schema_changes(old: &[(String, ColumnType)], new: &[(String, ColumnType)]) -> Vec<String> {
+    let in_file = |columns: &[(String, ColumnType)], name: &str| columns.iter().any(|(other_name, _)| other_name == name);
+    let mut changes = Vec::new();
+    for (name, column_type) in new.iter().filter(|(name, _)| !in_file(old, name)) {
+        changes.push(format!("added: {} ({})", name, column_type.name()));
+    }
+    for (name, column_type) in old.iter().filter(|(name, _)| !in_file(new, name)) {
+        changes.push(format!("removed: {} ({})", name, column_type.name()));
+    }
+    let common_old: Vec<&(String, ColumnType)> = old.iter().filter(|(name, _)| in_file(new, name)).collect();
+    let common_new: Vec<&(String, ColumnType)> = new.iter().filter(|(name, _)| in_file(old, name)).collect();
+    for (old_position, (name, _)) in common_old.iter().enumerate() {
+        let new_position = common_new.iter().position(|(new_name, _)| new_name == name).unwrap_or_default();
+        if new_position != old_position {
+            changes.push(format!("moved: {} {} -> {}", name, old_position + 1, new_position + 1));
+        }
+    }
+    for (name, old_type) in &common_old {
+        let new_type = common_new.iter().find(|(new_name, _)| new_name == name).map(|(_, column_type)| column_type);
+        if let Some(new_type) = new_type.filter(|&new_type| new_type != old_type) {
+            changes.push(format!("type: {} {} -> {}", name, old_type.name(), new_type.name()));
+        }
+    }
+    changes
+}
+
+/// The schema-diff command, printing the changes of the columns or that
+/// the schemas are the same
+pub fn run_schema(old_path: &str, new_path: &str, infer_rows: usize, options: &ReaderOptions) -> Result<(), Box<dyn Error>> {
+    let old = schema(input::open_input(Some(old_path), options)?, options.has_headers, infer_rows)?;
+    let new = schema(input::open_input(Some(new_path), options)?, options.has_headers, infer_rows)?;
+    let changes = schema_changes(&old, &new);
+    if changes.is_empty() {
+        println!("Same schema: {} columns", old.len());
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_column_order() {
+    let headers = StringRecord::from(vec!["id", "name", "age"]);
+    assert_eq!(column_order(&headers, &StringRecord::from(vec!["name", "age", "id"]), true), Ok(vec![2, 0, 1]));
+    assert!(column_order(&headers, &StringRecord::from(vec!["id", "name"]), true).is_err());
+    assert!(column_order(&headers, &StringRecord::from(vec!["id", "name", "city"]), true).is_err());
+    assert_eq!(column_order(&headers, &StringRecord::from(vec!["1", "2", "3"]), false), Ok(vec![0, 1, 2]));
+}
+
+#[test]
+fn test_diff_rows() {
+    use crate::input::test_input;
+
+    let old = test_input(&[&["id", "name"], &["1", "Ada"], &["2", "Bob"], &["2", "Bob"], &["3", "Cy"]]);
+    let new = test_input(&[&["name", "id"], &["Ada", "1"], &["Dee", "4"], &["Bob", "2"], &["Ada", "1"]]);
+    let mut changes = Vec::new();
+    let counts = diff_rows(old, new, &[1, 0], |sign, row| {
+        changes.push(format!("{} {}", sign, row.iter().collect::<Vec<_>>().join(",")));
+        Ok(())
+    }).unwrap();
+    assert_eq!(counts, Counts { added: 2, removed: 2, changed: 0, unchanged: 2 });
+    assert_eq!(changes, vec!["+ 4,Dee", "+ 1,Ada", "- 2,Bob", "- 3,Cy"]);
+}
+
+#[test]
+fn test_diff_keyed() {
+    use crate::input::test_input;
+
+    let old = test_input(&[&["id", "price", "qty"], &["41", "1", "2"], &["42", "10.0", "1"], &["43", "5", "5"]]);
+    let new = test_input(&[&["id", "qty", "price"], &["42", "2", "12.5"], &["44", "1", "1"], &["41", "2", "1"]]);
+    let mut changes = Vec::new();
+    let counts = diff_keyed(old, new, &[0, 2, 1], &[0], |change| {
+        changes.push(String::from(change));
+        Ok(())
+    }).unwrap();
+    assert_eq!(counts, Counts { added: 1, removed: 1, changed: 1, unchanged: 1 });
+    assert_eq!(changes, vec!["id=42: price 10.0 -> 12.5, qty 1 -> 2", "id=44: added", "id=43: removed"]);
+
+    let old = test_input(&[&["id", "day"], &["1", "mon"], &["1", "tue"]]);
+    let new = test_input(&[&["id", "day"], &["1", "mon"]]);
+    assert!(diff_keyed(old, new, &[0, 1], &[0], |_| Ok(())).is_err());
+}
+
+#[test]
+fn test_schema_changes() {
+    use crate::input::test_input;
+
+    let old = test_input(&[&["id", "name", "price", "phone"], &["1", "Ada", "10", "555"], &["2", "Bob", "", "556"]]);
+    let new = test_input(&[&["id", "price", "name", "email"], &["1", "10", "Ada", "a@x"], &["2", "12.5", "Bob", ""]]);
+    let old = schema(old, true, 100).unwrap();
+    assert_eq!(old[2], (String::from("price"), ColumnType::Integer));
+    let new = schema(new, true, 100).unwrap();
+    assert_eq!(schema_changes(&old, &new), vec![
+        "added: email (text)", "removed: phone (int)", "moved: name 2 -> 3", "moved: price 3 -> 2", "type: price int -> float",
+    ]);
+    assert!(schema_changes(&old, &old).is_empty());
+
+    // Only the first rows are read to infer the types
+    let new = test_input(&[&["id", "name", "price", "phone"], &["1", "Ada", "10", "555"], &["2", "Bob", "12.5", "556"]]);
+    assert!(schema_changes(&old, &schema(new, true, 1).unwrap()).is_empty());
+}
@@ -0,0 +1,587 @@
+mod parser;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use csv::StringRecord;
+use regex::Regex;
+use parser::{Condition, Expr, Operand, Operator};
+#[cfg(test)]
+use parser::DEFAULT_FUZZY_DISTANCE;
+use crate::value;
+
+pub enum RowFilterOperator {
+    Equal,
+    Lesser,
+    Greater,
+    EqualString,
+    NotEqual,
+    LesserOrEqual,
+    GreaterOrEqual,
+    Regex(Regex),
+    EqualIgnoreCase,
+    IsEmpty,
+    NotEmpty,
+    Fuzzy(usize),
+}
+
+impl PartialEq for RowFilterOperator {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            // Compiled regexes are compared by their source pattern
+            (RowFilterOperator::Regex(left), RowFilterOperator::Regex(right)) => left.as_str() == right.as_str(),
+            (RowFilterOperator::Fuzzy(left), RowFilterOperator::Fuzzy(right)) => left == right,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+/// Pseudo column holding the row number, usable in filters like "_row>100"
+pub const ROW_NUMBER_COLUMN: &str = "_row";
+
+pub struct RowFilter {
+    left_column: Option<usize>,
+    right_column: Option<usize>,  // set when comparing two columns, e.g. "end_time>start_time"
+    #[allow(dead_code)]
+    left_value: Option<String>,   // not used, but left for future flexibility
+    right_value: Option<String>,
+    operator: RowFilterOperator,
+    date_format: Option<String>,  // chrono format tried before the ISO ones
+}
+
+impl RowFilter {
+    // Builds a filter from a single condition, e.g. "price>=10.5"
+    #[cfg(test)]
+    pub fn new(filter_str: &str, col_idx_dict: &HashMap<String, usize>) -> Result<Self, String> {
+        let condition = parser::parse_condition(filter_str)?;
+        Self::from_condition(condition, col_idx_dict, filter_str)
+    }
+
+    // Resolves the column names of a parsed condition through the header index map
+    fn from_condition(condition: Condition, col_idx_dict: &HashMap<String, usize>,
+                      filter_str: &str) -> Result<Self, String> {
+        let left_column = *col_idx_dict.get(&condition.column)
+            .ok_or_else(|| format!("Unknown column '{}' in filter: {}", condition.column, filter_str))?;
+
+        let operator = match condition.operator {
+            Operator::EqualString => RowFilterOperator::EqualString,
+            Operator::Equal => RowFilterOperator::Equal,
+            Operator::NotEqual => RowFilterOperator::NotEqual,
+            Operator::Lesser => RowFilterOperator::Lesser,
+            Operator::LesserOrEqual => RowFilterOperator::LesserOrEqual,
+            Operator::Greater => RowFilterOperator::Greater,
+            Operator::GreaterOrEqual => RowFilterOperator::GreaterOrEqual,
+            Operator::EqualIgnoreCase => RowFilterOperator::EqualIgnoreCase,
+            Operator::IsEmpty => RowFilterOperator::IsEmpty,
+            Operator::NotEmpty => RowFilterOperator::NotEmpty,
+            Operator::Fuzzy(distance) => RowFilterOperator::Fuzzy(distance),
+            Operator::Regex => {
+                let pattern = condition.value.as_ref().map(Operand::as_str).unwrap_or_default();
+                let regex = Regex::new(pattern)
+                    .map_err(|err| format!("Invalid regex '{}' in filter {}: {}", pattern, filter_str, err))?;
+                RowFilterOperator::Regex(regex)
+            },
+            // Globs are matched with the equivalent regex
+            Operator::Like => {
+                let glob = condition.value.as_ref().map(Operand::as_str).unwrap_or_default();
+                let regex = Regex::new(&glob_to_regex(glob))
+                    .map_err(|err| format!("Invalid pattern '{}' in filter {}: {}", glob, filter_str, err))?;
+                RowFilterOperator::Regex(regex)
+            },
+        };
+
+        // The right side is another column when it's a bare header name,
+        // quoted values and regexes are always taken literally
+        let (right_column, right_value) = match condition.value {
+            None => (None, None),
+            Some(Operand::Bare(value)) if !matches!(operator, RowFilterOperator::Regex(_)) => {
+                match col_idx_dict.get(&value) {
+                    Some(&right_column) => (Some(right_column), None),
+                    None => (None, Some(value)),
+                }
+            },
+            Some(Operand::Bare(value)) | Some(Operand::Quoted(value)) => (None, Some(value)),
+        };
+
+        Ok(Self {
+            left_column: Some(left_column),
+            right_column,
+            left_value: None,
+            right_value,
+            operator,
+            date_format: None,
+        })
+    }
+
+    // Value of the right side of the filter for the given row
+    fn right_of<'a>(&'a self, row: &'a StringRecord) -> &'a str {
+        match self.right_column {
+            Some(right_column) => row.get(right_column).unwrap(),
+            None => self.right_value.as_ref().unwrap().as_str(),
+        }
+    }
+
+    // Compares both sides through the value typing layer: as numbers, as
+    // dates or as strings
+    fn compare(&self, row: &StringRecord) -> Ordering {
+        let left_value = row.get(self.left_column.unwrap()).unwrap();
+        value::compare(left_value, self.right_of(row), self.date_format.as_deref())
+    }
+
+    pub fn accepts(&self, row: &StringRecord) -> bool {
+        match self.operator {
+            RowFilterOperator::EqualString => {
+                let left_value = row.get(self.left_column.unwrap()).unwrap();
+                let right_value = self.right_of(row);
+                left_value == right_value
+            },
+            RowFilterOperator::Equal => self.compare(row) == Ordering::Equal,
+            RowFilterOperator::Lesser => self.compare(row) == Ordering::Less,
+            RowFilterOperator::Greater => self.compare(row) == Ordering::Greater,
+            RowFilterOperator::LesserOrEqual => self.compare(row) != Ordering::Greater,
+            RowFilterOperator::GreaterOrEqual => self.compare(row) != Ordering::Less,
+            RowFilterOperator::Regex(ref regex) => {
+                let left_value = row.get(self.left_column.unwrap()).unwrap();
+                regex.is_match(left_value)
+            },
+            RowFilterOperator::Fuzzy(distance) => {
+                let left_value = row.get(self.left_column.unwrap()).unwrap();
+                strsim::levenshtein(left_value, self.right_of(row)) <= distance
+            },
+            RowFilterOperator::EqualIgnoreCase => {
+                let left_value = row.get(self.left_column.unwrap()).unwrap();
+                let right_value = self.right_of(row);
+                left_value.to_lowercase() == right_value.to_lowercase()
+            },
+            // Fields with only whitespace count as empty
+            RowFilterOperator::IsEmpty => row.get(self.left_column.unwrap()).unwrap().trim().is_empty(),
+            RowFilterOperator::NotEmpty => !row.get(self.left_column.unwrap()).unwrap().trim().is_empty(),
+            // Compares numerically when both sides are numbers, so "3.0" != "3" is false
+            RowFilterOperator::NotEqual => self.compare(row) != Ordering::Equal,
+        }
+    }
+}
+
+/// Translates a shell style glob into an anchored regex: * matches any text,
+/// ? any single character, and [abc], [a-z] or [!abc] a character class
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            },
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// A boolean combination of row filters, e.g. `(status=ok OR status=warn) AND size>1000`.
+/// NOT (or `!`) binds tightest, then AND, then OR, and filters separated by
+/// commas must all match.
+pub enum FilterExpr {
+    Condition(RowFilter),
+    Not(Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+impl FilterExpr {
+    pub fn parse(filter_str: &str, col_idx_dict: &HashMap<String, usize>,
+                 date_format: Option<&str>) -> Result<Self, String> {
+        let expr = parser::parse(filter_str)?;
+        Self::from_expr(expr, col_idx_dict, date_format, filter_str)
+    }
+
+    fn from_expr(expr: Expr, col_idx_dict: &HashMap<String, usize>,
+                 date_format: Option<&str>, filter_str: &str) -> Result<Self, String> {
+        let from_expr = |expr: Box<Expr>| Self::from_expr(*expr, col_idx_dict, date_format, filter_str).map(Box::new);
+        Ok(match expr {
+            Expr::Condition(condition) => {
+                let mut filter = RowFilter::from_condition(condition, col_idx_dict, filter_str)?;
+                filter.date_format = date_format.map(String::from);
+                FilterExpr::Condition(filter)
+            },
+            Expr::Not(expr) => FilterExpr::Not(from_expr(expr)?),
+            Expr::And(left, right) => FilterExpr::And(from_expr(left)?, from_expr(right)?),
+            Expr::Or(left, right) => FilterExpr::Or(from_expr(left)?, from_expr(right)?),
+        })
+    }
+
+    pub fn accepts(&self, row: &StringRecord) -> bool {
+        match self {
+            FilterExpr::Condition(filter) => filter.accepts(row),
+            FilterExpr::Not(expr) => !expr.accepts(row),
+            FilterExpr::And(left, right) => left.accepts(row) && right.accepts(row),
+            FilterExpr::Or(left, right) => left.accepts(row) || right.accepts(row),
+        }
+    }
+}
+
+#[test]
+fn test_equal_row_filter_constructor() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("IMAGE_NAME"), 2);
+    let row_filter = RowFilter::new("IMAGE_NAME=file1.png", &hash_map).unwrap();
+
+    // The operator should be EqualString
+    assert!(row_filter.operator == RowFilterOperator::EqualString);
+    assert_eq!(row_filter.left_column, Some(2));
+    assert_eq!(row_filter.right_column, None);
+    assert_eq!(row_filter.left_value, None);
+    assert_eq!(row_filter.right_value, Some(String::from("file1.png")));
+}
+
+#[test]
+fn test_equal_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("IMAGE_NAME"), 2);
+    let row_filter = RowFilter::new("IMAGE_NAME=file1.png", &hash_map).unwrap();
+
+    let record = StringRecord::from(vec!["someContentInFirstColumn", "someContentInSecondColumn", "file1.png"]);
+
+    let record2 = StringRecord::from(vec!["someContentInFirstColumn", "someContentInSecondColumn", "file2.png"]);
+
+    // First record should be accepted
+    assert!(row_filter.accepts(&record));
+    // First record should not be accepted
+    assert!(!row_filter.accepts(&record2));
+}
+
+#[test]
+fn test_lesser_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("image_number"), 0);
+    let row_filter = RowFilter::new("image_number<3", &hash_map).unwrap();
+
+    assert!(row_filter.operator == RowFilterOperator::Lesser);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["2"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["-1.5"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["3"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["10"])));
+}
+
+#[test]
+fn test_greater_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("image_number"), 0);
+    let row_filter = RowFilter::new("image_number>3", &hash_map).unwrap();
+
+    assert!(row_filter.operator == RowFilterOperator::Greater);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["10"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["3"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["2.9"])));
+}
+
+#[test]
+fn test_numeric_equal_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("image_number"), 0);
+    let row_filter = RowFilter::new("image_number==3", &hash_map).unwrap();
+
+    // Both sides are coerced to floats, so "3.0" equals "3"
+    assert!(row_filter.operator == RowFilterOperator::Equal);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["3.0"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["4"])));
+}
+
+#[test]
+fn test_not_equal_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("status"), 0);
+    hash_map.insert(String::from("image_number"), 1);
+
+    let row_filter = RowFilter::new("status!=failed", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::NotEqual);
+    assert_eq!(row_filter.right_value, Some(String::from("failed")));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["ok", "1"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["failed", "1"])));
+
+    // Numbers are compared as numbers
+    let row_filter = RowFilter::new("image_number!=3", &hash_map).unwrap();
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["ok", "3.0"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["ok", "4"])));
+}
+
+#[test]
+fn test_or_equal_row_filters_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("price"), 0);
+
+    let row_filter = RowFilter::new("price>=10.5", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::GreaterOrEqual);
+    assert_eq!(row_filter.right_value, Some(String::from("10.5")));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["10.5"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["11"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["10"])));
+
+    let row_filter = RowFilter::new("price<=10.5", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::LesserOrEqual);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["10.5"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["3"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["11"])));
+}
+
+#[test]
+fn test_regex_row_filter_accepts_method() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("name"), 0);
+    let row_filter = RowFilter::new("name~^img_[0-9]+\\.png$", &hash_map).unwrap();
+
+    assert!(row_filter.operator == RowFilterOperator::Regex(Regex::new("^img_[0-9]+\\.png$").unwrap()));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["img_12.png"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["img_12.jpg"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["img_.png"])));
+}
+
+#[test]
+fn test_filter_expr_precedence() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("status"), 0);
+    hash_map.insert(String::from("size"), 1);
+
+    // AND binds tighter: status=ok OR (status=warn AND size>1000)
+    let expr = FilterExpr::parse("status=ok OR status=warn AND size>1000", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["ok", "1"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["warn", "1"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["warn", "2000"])));
+
+    let expr = FilterExpr::parse("(status=ok OR status=warn) AND size>1000", &hash_map, None).unwrap();
+    assert!(!expr.accepts(&StringRecord::from(vec!["ok", "1"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["ok", "2000"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["warn", "2000"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["failed", "2000"])));
+}
+
+#[test]
+fn test_filter_expr_commas_and_spaces() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("status"), 0);
+    hash_map.insert(String::from("size"), 1);
+
+    // Commas keep working as an implicit AND, and spaces around operators are allowed
+    let expr = FilterExpr::parse("status=ok OR status=warn, size > 1000", &hash_map, None).unwrap();
+    assert!(!expr.accepts(&StringRecord::from(vec!["ok", "1"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["warn", "2000"])));
+
+    // Parentheses inside a regex belong to the pattern
+    let expr = FilterExpr::parse("(status~^(ok|warn)$)", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["warn", "1"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["failed", "1"])));
+}
+
+#[test]
+fn test_filter_expr_errors() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("status"), 0);
+
+    assert!(FilterExpr::parse("(status=ok", &hash_map, None).is_err());
+    assert!(FilterExpr::parse("status=ok)", &hash_map, None).is_err());
+    assert!(FilterExpr::parse("status=ok AND", &hash_map, None).is_err());
+    assert!(FilterExpr::parse("OR status=ok", &hash_map, None).is_err());
+}
+
+#[test]
+fn test_column_to_column_row_filter() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("start_time"), 0);
+    hash_map.insert(String::from("end_time"), 1);
+    hash_map.insert(String::from("expected"), 2);
+    hash_map.insert(String::from("actual"), 3);
+
+    let row_filter = RowFilter::new("end_time>start_time", &hash_map).unwrap();
+    assert_eq!(row_filter.right_column, Some(0));
+    assert_eq!(row_filter.right_value, None);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["10", "20", "a", "a"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["20", "10", "a", "a"])));
+
+    let row_filter = RowFilter::new("actual=expected", &hash_map).unwrap();
+    assert_eq!(row_filter.right_column, Some(2));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["1", "2", "same", "same"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["1", "2", "same", "other"])));
+}
+
+#[test]
+fn test_equal_ignore_case_row_filter() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("extension"), 0);
+    let row_filter = RowFilter::new("extension~=png", &hash_map).unwrap();
+
+    assert!(row_filter.operator == RowFilterOperator::EqualIgnoreCase);
+    assert!(row_filter.accepts(&StringRecord::from(vec!["PNG"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["png"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["jpg"])));
+}
+
+#[test]
+fn test_empty_row_filters() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("email"), 0);
+
+    let row_filter = RowFilter::new("email isempty", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::IsEmpty);
+    assert!(row_filter.accepts(&StringRecord::from(vec![""])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["  "])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["a@b.com"])));
+
+    let row_filter = RowFilter::new("email notempty", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::NotEmpty);
+    assert!(!row_filter.accepts(&StringRecord::from(vec![""])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["a@b.com"])));
+}
+
+#[test]
+fn test_filter_expr_negation() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("name"), 0);
+    hash_map.insert(String::from("size"), 1);
+
+    let expr = FilterExpr::parse("NOT name~temp", &hash_map, None).unwrap();
+    assert!(!expr.accepts(&StringRecord::from(vec!["temp_file", "1"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["file", "1"])));
+
+    // NOT binds tighter than AND
+    let expr = FilterExpr::parse("!name~temp AND size>10", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["file", "20"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["file", "1"])));
+
+    let expr = FilterExpr::parse("!(name~temp OR size>10)", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["file", "1"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["file", "20"])));
+
+    // "!=" is still an operator
+    let expr = FilterExpr::parse("name!=file", &hash_map, None).unwrap();
+    assert!(!expr.accepts(&StringRecord::from(vec!["file", "1"])));
+}
+
+#[test]
+fn test_date_row_filters() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("created_at"), 0);
+
+    let expr = FilterExpr::parse("created_at>2024-01-01", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["2024-03-15"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["2024-01-01 10:30:00"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["2024-01-01T00:00:01Z"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["2023-12-31"])));
+    // Values that are neither numbers nor dates are compared as strings
+    assert!(expr.accepts(&StringRecord::from(vec!["yesterday"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["1999"])));
+
+    // With a custom format both sides are parsed with it
+    let expr = FilterExpr::parse("created_at<=15/03/2024", &hash_map, Some("%d/%m/%Y")).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["14/03/2024"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["15/03/2024"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["01/04/2024"])));
+}
+
+#[test]
+fn test_quoted_filter_values() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("title"), 0);
+    hash_map.insert(String::from("size"), 1);
+    hash_map.insert(String::from("page title"), 2);
+
+    // Operators, commas and spaces inside quotes are part of the value
+    let expr = FilterExpr::parse(r#"title="a=b, c""#, &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["a=b, c", "1", ""])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["a", "1", ""])));
+
+    let expr = FilterExpr::parse(r#"title='x<y' OR size>3, title!="(z)""#, &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["x<y", "1", ""])));
+    assert!(expr.accepts(&StringRecord::from(vec!["w", "4", ""])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["(z)", "4", ""])));
+
+    // Escaped quotes
+    let expr = FilterExpr::parse(r#"title="say \"hi\"""#, &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec![r#"say "hi""#, "1", ""])));
+
+    // Quoted columns, and quoted values are never taken as columns
+    let expr = FilterExpr::parse(r#""page title"="size""#, &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["", "1", "size"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["", "1", "1"])));
+}
+
+#[test]
+fn test_mixed_type_row_filters() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("size"), 0);
+
+    let row_filter = RowFilter::new("size<9", &hash_map).unwrap();
+    // "10" < "9" as strings, but not as numbers
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["10"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["8.5"])));
+    // Empty and text fields don't panic, they are compared as strings
+    assert!(row_filter.accepts(&StringRecord::from(vec![""])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["big"])));
+}
+
+#[test]
+fn test_between_row_filter() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("price"), 0);
+    hash_map.insert(String::from(ROW_NUMBER_COLUMN), 1);
+
+    let expr = FilterExpr::parse("_row between 50 and 60", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["1", "50"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["1", "55"])));
+    assert!(expr.accepts(&StringRecord::from(vec!["1", "60"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["1", "61"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["1", "9"])));
+
+    let expr = FilterExpr::parse("price between 1 AND 2 AND _row>1", &hash_map, None).unwrap();
+    assert!(expr.accepts(&StringRecord::from(vec!["1.5", "2"])));
+    assert!(!expr.accepts(&StringRecord::from(vec!["1.5", "1"])));
+}
+
+#[test]
+fn test_fuzzy_row_filter() {
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("city"), 0);
+
+    let row_filter = RowFilter::new("city fuzzy:2 Chcago", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::Fuzzy(2));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["Chicago"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["Chcago"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec!["Chicag0"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["Boston"])));
+
+    let row_filter = RowFilter::new("city fuzzy Chcago", &hash_map).unwrap();
+    assert!(row_filter.operator == RowFilterOperator::Fuzzy(DEFAULT_FUZZY_DISTANCE));
+}
+
+#[test]
+fn test_like_row_filter() {
+    assert_eq!(glob_to_regex("*.png"), "^.*\\.png$");
+    assert_eq!(glob_to_regex("img_?[!0-9].gif"), "^img_.[^0-9]\\.gif$");
+
+    let mut hash_map = HashMap::<String, usize>::new();
+    hash_map.insert(String::from("filename"), 0);
+
+    let row_filter = RowFilter::new("filename like *.png", &hash_map).unwrap();
+    assert!(row_filter.accepts(&StringRecord::from(vec!["file1.png"])));
+    assert!(row_filter.accepts(&StringRecord::from(vec![".png"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["file1.png.bak"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["file1_png"])));
+
+    let row_filter = RowFilter::new("filename like 'file[12]*'", &hash_map).unwrap();
+    assert!(row_filter.accepts(&StringRecord::from(vec!["file2.jpg"])));
+    assert!(!row_filter.accepts(&StringRecord::from(vec!["file3.jpg"])));
+}
@@ -0,0 +1,410 @@
+// Parser for the --filter language. It turns a filter string into an Expr
+// tree, without knowing anything about the CSV headers.
+//
+// filters   := or (',' or)*
+// or        := and ('OR' and)*
+// and       := primary ('AND' primary)*
+// primary   := ('NOT' | '!') primary | '(' or ')' | condition
+// condition := name operator value | name ('isempty' | 'notempty')
+//            | name 'between' value 'and' value
+//            | name 'fuzzy' [':' distance] value
+//            | name 'like' value
+// operator  := '=' | '==' | '!=' | '<' | '<=' | '>' | '>=' | '~' | '~='
+//
+// Names and values can be quoted with " or ', and \ escapes the next
+// character inside quotes. Unquoted values run until a space, a comma or a
+// ')' that was not opened inside the value, so regexes like ^(a|b)$ work.
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Condition(Condition),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Condition {
+    pub column: String,
+    pub operator: Operator,
+    pub value: Option<Operand>,  // None for unary operators
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    EqualString,      // =
+    Equal,            // ==
+    NotEqual,         // !=
+    Lesser,           // <
+    LesserOrEqual,    // <=
+    Greater,          // >
+    GreaterOrEqual,   // >=
+    Regex,            // ~
+    EqualIgnoreCase,  // ~=
+    IsEmpty,
+    NotEmpty,
+    Fuzzy(usize),      // fuzzy:N, Levenshtein distance
+    Like,              // shell style glob
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    // Quoted values are always literals
+    Quoted(String),
+    // Bare values may also name a column
+    Bare(String),
+}
+
+impl Operand {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operand::Quoted(value) | Operand::Bare(value) => value,
+        }
+    }
+}
+
+// Longest symbols first, so "<=" is never read as "<"
+const SYMBOL_OPERATORS: [(&str, Operator); 9] = [
+    ("~=", Operator::EqualIgnoreCase),
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("<=", Operator::LesserOrEqual),
+    (">=", Operator::GreaterOrEqual),
+    ("~", Operator::Regex),
+    ("<", Operator::Lesser),
+    (">", Operator::Greater),
+    ("=", Operator::EqualString),
+];
+
+// Distance used by "fuzzy" without ":N"
+pub const DEFAULT_FUZZY_DISTANCE: usize = 2;
+
+// Characters that end an unquoted column name
+const NAME_DELIMITERS: &str = "=<>!~,()\"'";
+
+/// Parses a whole filter expression
+pub fn parse(input: &str) -> Result<Expr, String> {
+    let mut parser = Parser { input, position: 0 };
+    let expr = parser.parse_filters()?;
+    parser.expect_end()?;
+    Ok(expr)
+}
+
+/// Parses a single condition, like "price>=10.5"
+#[cfg(test)]
+pub fn parse_condition(input: &str) -> Result<Condition, String> {
+    let mut parser = Parser { input, position: 0 };
+    let condition = parser.parse_condition()?;
+    parser.expect_end()?;
+    match condition {
+        Expr::Condition(condition) => Ok(condition),
+        _ => Err(format!("Expected a single condition in filter: {}", input)),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,  // byte offset in input
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> String {
+        let position = self.input[..self.position].chars().count() + 1;
+        format!("{} at position {} in filter: {}", message, position, self.input)
+    }
+
+    fn eat_char(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    // Keywords must be whole words, so a column named "ORDER" is not "OR"
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if !rest.starts_with(keyword) {
+            return false;
+        }
+        match rest[keyword.len()..].chars().next() {
+            None | Some('(') | Some(')') => {},
+            Some(c) if c.is_whitespace() => {},
+            Some(_) => return false,
+        }
+        self.position += keyword.len();
+        true
+    }
+
+    fn expect_end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(')') => Err(self.error("Unbalanced ')'")),
+            Some(_) => Err(self.error(&format!("Unexpected '{}'", self.rest()))),
+        }
+    }
+
+    // filters := or (',' or)*
+    fn parse_filters(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_or()?;
+        while self.eat_char(',') {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_or()?));
+        }
+        Ok(expr)
+    }
+
+    // or := and ('OR' and)*
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    // and := primary ('AND' primary)*
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_primary()?;
+        while self.eat_keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_primary()?));
+        }
+        Ok(expr)
+    }
+
+    // primary := ('NOT' | '!') primary | '(' or ')' | condition
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        if self.eat_keyword("NOT") || self.eat_char('!') {
+            return Ok(Expr::Not(Box::new(self.parse_primary()?)));
+        }
+        if self.eat_char('(') {
+            let expr = self.parse_or()?;
+            if !self.eat_char(')') {
+                return Err(self.error("Missing ')'"));
+            }
+            return Ok(expr);
+        }
+        self.parse_condition()
+    }
+
+    // condition := name operator value | name ('isempty' | 'notempty')
+    //            | name 'between' value 'and' value
+    //            | name 'fuzzy' [':' distance] value
+    //            | name 'like' value
+    fn parse_condition(&mut self) -> Result<Expr, String> {
+        let column = self.parse_name()?;
+
+        self.skip_whitespace();
+        let symbol = SYMBOL_OPERATORS.iter().find(|(symbol, _)| self.rest().starts_with(symbol));
+        if let Some(&(symbol, operator)) = symbol {
+            self.position += symbol.len();
+            let value = self.parse_value(symbol)?;
+            return Ok(Expr::Condition(Condition { column, operator, value: Some(value) }));
+        }
+
+        // "x between a and b" is turned into "x>=a AND x<=b"
+        if self.eat_keyword("between") {
+            let lower = self.parse_value("between")?;
+            if !self.eat_keyword("and") && !self.eat_keyword("AND") {
+                return Err(self.error("Expected 'and' in between"));
+            }
+            let upper = self.parse_value("and")?;
+            return Ok(Expr::And(
+                Box::new(Expr::Condition(Condition { column: column.clone(), operator: Operator::GreaterOrEqual, value: Some(lower) })),
+                Box::new(Expr::Condition(Condition { column, operator: Operator::LesserOrEqual, value: Some(upper) })),
+            ));
+        }
+
+        if let Some(distance) = self.parse_fuzzy()? {
+            let value = self.parse_value("fuzzy")?;
+            return Ok(Expr::Condition(Condition { column, operator: Operator::Fuzzy(distance), value: Some(value) }));
+        }
+
+        if self.eat_keyword("like") {
+            let value = self.parse_value("like")?;
+            return Ok(Expr::Condition(Condition { column, operator: Operator::Like, value: Some(value) }));
+        }
+
+        let operator = if self.eat_keyword("isempty") {
+            Operator::IsEmpty
+        } else if self.eat_keyword("notempty") {
+            Operator::NotEmpty
+        } else {
+            return Err(self.error(&format!("Expected an operator after column '{}'", column)));
+        };
+        Ok(Expr::Condition(Condition { column, operator, value: None }))
+    }
+
+    // 'fuzzy' [':' distance]
+    fn parse_fuzzy(&mut self) -> Result<Option<usize>, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if !rest.starts_with("fuzzy:") {
+            return Ok(self.eat_keyword("fuzzy").then_some(DEFAULT_FUZZY_DISTANCE));
+        }
+        self.position += "fuzzy:".len();
+        let digits = self.rest().find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest().len());
+        let distance = self.rest()[..digits].parse::<usize>()
+            .map_err(|_| self.error("Expected a distance after 'fuzzy:'"))?;
+        self.position += digits;
+        Ok(Some(distance))
+    }
+
+    fn parse_name(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        if let Some(quote) = self.peek().filter(|&c| c == '"' || c == '\'') {
+            return self.parse_quoted(quote);
+        }
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| c.is_whitespace() || NAME_DELIMITERS.contains(c))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("Expected a column name"));
+        }
+        self.position += length;
+        Ok(String::from(&rest[..length]))
+    }
+
+    fn parse_value(&mut self, operator: &str) -> Result<Operand, String> {
+        self.skip_whitespace();
+        if let Some(quote) = self.peek().filter(|&c| c == '"' || c == '\'') {
+            return Ok(Operand::Quoted(self.parse_quoted(quote)?));
+        }
+
+        let rest = self.rest();
+        let mut depth = 0;
+        let mut length = rest.len();
+        for (index, c) in rest.char_indices() {
+            if c.is_whitespace() || c == ',' || (c == ')' && depth == 0) {
+                length = index;
+                break;
+            }
+            if c == '(' {
+                depth += 1;
+            } else if c == ')' {
+                depth -= 1;
+            }
+        }
+        if length == 0 {
+            return Err(self.error(&format!("Expected a value after '{}'", operator)));
+        }
+        self.position += length;
+        Ok(Operand::Bare(String::from(&rest[..length])))
+    }
+
+    fn parse_quoted(&mut self, quote: char) -> Result<String, String> {
+        let start = self.position;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            if c == quote {
+                self.position += index + c.len_utf8();
+                return Ok(value);
+            }
+            if c == '\\' {
+                value.extend(chars.next().map(|(_, escaped)| escaped));
+            } else {
+                value.push(c);
+            }
+        }
+        self.position = start;
+        Err(self.error("Unterminated quote"))
+    }
+}
+
+#[cfg(test)]
+fn condition(column: &str, operator: Operator, value: Option<Operand>) -> Expr {
+    Expr::Condition(Condition { column: String::from(column), operator, value })
+}
+
+#[cfg(test)]
+fn bare(value: &str) -> Option<Operand> {
+    Some(Operand::Bare(String::from(value)))
+}
+
+#[test]
+fn test_parse_operators() {
+    let cases = [
+        ("a=1", Operator::EqualString),
+        ("a==1", Operator::Equal),
+        ("a!=1", Operator::NotEqual),
+        ("a<1", Operator::Lesser),
+        ("a<=1", Operator::LesserOrEqual),
+        ("a>1", Operator::Greater),
+        ("a>=1", Operator::GreaterOrEqual),
+        ("a~1", Operator::Regex),
+        ("a~=1", Operator::EqualIgnoreCase),
+    ];
+    for (input, operator) in cases {
+        assert_eq!(parse(input), Ok(condition("a", operator, bare("1"))), "{}", input);
+    }
+    assert_eq!(parse("email notempty"), Ok(condition("email", Operator::NotEmpty, None)));
+    assert_eq!(parse("email isempty"), Ok(condition("email", Operator::IsEmpty, None)));
+    assert_eq!(parse("city fuzzy:3 Chcago"), Ok(condition("city", Operator::Fuzzy(3), bare("Chcago"))));
+    assert_eq!(parse("filename like *.png"), Ok(condition("filename", Operator::Like, bare("*.png"))));
+    assert_eq!(parse("city fuzzy Chcago"), Ok(condition("city", Operator::Fuzzy(DEFAULT_FUZZY_DISTANCE), bare("Chcago"))));
+    assert_eq!(
+        parse("_row between 50 and 60"),
+        Ok(Expr::And(
+            Box::new(condition("_row", Operator::GreaterOrEqual, bare("50"))),
+            Box::new(condition("_row", Operator::LesserOrEqual, bare("60"))),
+        ))
+    );
+}
+
+#[test]
+fn test_parse_values() {
+    // Whatever follows the operator belongs to the value
+    assert_eq!(parse("title=x<y"), Ok(condition("title", Operator::EqualString, bare("x<y"))));
+    assert_eq!(parse("size > 1000"), Ok(condition("size", Operator::Greater, bare("1000"))));
+    assert_eq!(parse("name~^(a|b)$"), Ok(condition("name", Operator::Regex, bare("^(a|b)$"))));
+    assert_eq!(
+        parse(r#""page title"='say \'hi\', ok'"#),
+        Ok(condition("page title", Operator::EqualString, Some(Operand::Quoted(String::from("say 'hi', ok")))))
+    );
+}
+
+#[test]
+fn test_parse_expressions() {
+    let a = || Box::new(condition("a", Operator::EqualString, bare("1")));
+    let b = || Box::new(condition("b", Operator::EqualString, bare("2")));
+    let c = || Box::new(condition("c", Operator::EqualString, bare("3")));
+
+    assert_eq!(parse("a=1 OR b=2 AND c=3"), Ok(Expr::Or(a(), Box::new(Expr::And(b(), c())))));
+    assert_eq!(parse("(a=1 OR b=2) AND c=3"), Ok(Expr::And(Box::new(Expr::Or(a(), b())), c())));
+    assert_eq!(parse("a=1 OR b=2, c=3"), Ok(Expr::And(Box::new(Expr::Or(a(), b())), c())));
+    assert_eq!(parse("NOT a=1 AND !b=2"), Ok(Expr::And(Box::new(Expr::Not(a())), Box::new(Expr::Not(b())))));
+    assert_eq!(parse("(a=1)"), Ok(*a()));
+    // Keywords must be whole words
+    assert_eq!(parse("ORDER=1"), Ok(condition("ORDER", Operator::EqualString, bare("1"))));
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse("status ok"), Err(String::from("Expected an operator after column 'status' at position 8 in filter: status ok")));
+    assert_eq!(parse("status="), Err(String::from("Expected a value after '=' at position 8 in filter: status=")));
+    assert_eq!(parse("(a=1"), Err(String::from("Missing ')' at position 5 in filter: (a=1")));
+    assert_eq!(parse("a=1)"), Err(String::from("Unbalanced ')' at position 4 in filter: a=1)")));
+    assert_eq!(parse("a=1 b=2"), Err(String::from("Unexpected 'b=2' at position 5 in filter: a=1 b=2")));
+    assert_eq!(parse("=1"), Err(String::from("Expected a column name at position 1 in filter: =1")));
+    assert_eq!(parse("a=1 AND"), Err(String::from("Expected a column name at position 8 in filter: a=1 AND")));
+    assert_eq!(parse("a=\"1"), Err(String::from("Unterminated quote at position 3 in filter: a=\"1")));
+    assert_eq!(parse("a fuzzy:x b"), Err(String::from("Expected a distance after 'fuzzy:' at position 9 in filter: a fuzzy:x b")));
+    assert_eq!(parse("a between 1 2"), Err(String::from("Expected 'and' in between at position 13 in filter: a between 1 2")));
+}
@@ -0,0 +1,719 @@
+mod estimate;
+mod fwf;
+mod jsonl;
+mod parquet;
+#[cfg(feature = "s3")]
+mod s3;
+mod tail;
+mod xlsx;
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use csv::StringRecord;
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+
+pub use estimate::estimate_rows;
+pub use fwf::{parse_fwf, FwfSpec};
+pub use tail::tail;
+
+/// Path that reads from stdin, besides not giving a file at all
+pub const STDIN_PATH: &str = "-";
+
+/// Compression of the input, for --compression
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Compression given by the extension of the file
+    pub fn from_path(path: &str) -> Self {
+        match without_query(path).rsplit_once('.').map(|(_, extension)| extension) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("bz2") => Compression::Bzip2,
+            Some("xz") => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Expands the glob patterns of the inputs, like 'logs/2024-*.csv', in
+/// alphabetical order. Other paths are kept as they are
+pub fn expand_globs(paths: &[String]) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if !path.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+        let matches = glob::glob(path)?
+            .map(|entry| entry.map(|path| path.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<String>, _>>()?;
+        if matches.is_empty() {
+            return Err(format!("No files match '{}'", path).into());
+        }
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+/// Format of the input, for --input-format
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum InputFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+    Xlsx,
+}
+
+impl InputFormat {
+    /// Format given by the extension of the file, also when compressed.
+    /// CSV by default
+    pub fn from_path(path: Option<&str>) -> Self {
+        match extension(path) {
+            Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+            Some("parquet") => InputFormat::Parquet,
+            Some("xlsx") => InputFormat::Xlsx,
+            _ => InputFormat::Csv,
+        }
+    }
+}
+
+/// How the inputs are read, from the command line options
+#[derive(Clone)]
+pub struct ReaderOptions {
+    /// Given by the extension of each input when missing
+    pub format: Option<InputFormat>,
+    pub compression: Option<Compression>,
+    pub encoding: Option<&'static Encoding>,
+    pub skip_lines: usize,
+    /// Guessed for each input when missing and sniff is set
+    pub delimiter: Option<u8>,
+    pub sniff: bool,
+    pub has_headers: bool,
+    pub comment: Option<u8>,
+    /// Keep reading the input as it grows, like tail -f
+    pub follow: bool,
+    /// Pad the short rows of CSV inputs with empty fields and truncate the long ones
+    pub flexible: bool,
+    /// Sheet of the xlsx inputs, the first one when missing
+    pub sheet: Option<String>,
+    /// Columns of fixed-width inputs, read instead of any other format
+    pub fwf: Option<FwfSpec>,
+}
+
+impl ReaderOptions {
+    /// Options for another file read along the inputs, like the one of
+    /// --join. Its format and compression come from its extension, and it's
+    /// read once from its start
+    pub fn for_other_file(&self) -> Self {
+        Self {
+            format: None,
+            compression: None,
+            skip_lines: 0,
+            follow: false,
+            sheet: None,
+            fwf: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// Records of an input, read as they are needed
+pub type RecordIter = Box<dyn Iterator<Item = Result<StringRecord, Box<dyn Error>>>>;
+
+/// Record along with its 0-based index in the inputs
+pub type IndexedRecord = Result<(usize, StringRecord), Box<dyn Error>>;
+
+/// An opened input of any format, as the rows of a CSV file
+pub struct Input {
+    pub headers: StringRecord,
+    pub records: RecordIter,
+    /// Dialect guessed for CSV inputs read with sniff
+    pub sniffed: Option<Dialect>,
+}
+
+/// Opens an input in its format, given by --input-format or the extension
+pub fn open_input(path: Option<&str>, options: &ReaderOptions) -> Result<Input, Box<dyn Error>> {
+    if let Some(spec) = &options.fwf {
+        return fwf::open(read(path, options)?, spec, options.has_headers);
+    }
+    match options.format.unwrap_or_else(|| InputFormat::from_path(path)) {
+        InputFormat::Csv => open_csv(path, options),
+        InputFormat::Jsonl => jsonl::open(read(path, options)?),
+        InputFormat::Parquet => parquet::open(path, options.compression),
+        InputFormat::Xlsx => xlsx::open(open(path, options.compression)?, options.sheet.as_deref(), options.has_headers),
+    }
+}
+
+/// Opens the text of an input: decompressed, decoded and without the
+/// skipped lines
+fn read(path: Option<&str>, options: &ReaderOptions) -> io::Result<Box<dyn Read>> {
+    let input = match options.follow {
+        true => follow(path, options.compression)?,
+        false => open(path, options.compression)?,
+    };
+    skip_lines(decode(input, options.encoding), options.skip_lines)
+}
+
+/// Opens an input as CSV, with the delimiter of --delimiter, the guessed or
+/// the default one
+fn open_csv(path: Option<&str>, options: &ReaderOptions) -> Result<Input, Box<dyn Error>> {
+    let input = read(path, options)?;
+    let default_delimiter = default_delimiter(path);
+    // Sniffing would wait for a full sample of a followed input
+    let sniffed = !options.follow && (options.sniff || (options.delimiter.is_none() && has_ambiguous_extension(path)));
+    let (dialect, input): (Dialect, Box<dyn Read>) = match sniffed {
+        true => {
+            let (sample, input) = peek(input)?;
+            (sniff(&sample, default_delimiter), Box::new(input))
+        },
+        false => (Dialect { delimiter: options.delimiter.unwrap_or(default_delimiter), quote: b'"' }, input),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .has_headers(options.has_headers)
+        .comment(options.comment)
+        .flexible(options.flexible)
+        .from_reader(input);
+    let headers = reader.headers()?.clone();
+    let columns = headers.len();
+    let records = reader.into_records().map(move |result| match result {
+        Ok(record) if record.len() != columns => Ok(fit(record, columns)),
+        result => result.map_err(Box::from),
+    });
+    Ok(Input { headers, records: Box::new(records), sniffed: sniffed.then_some(dialect) })
+}
+
+/// Pads the record with empty fields or truncates it to the given columns
+fn fit(record: StringRecord, columns: usize) -> StringRecord {
+    record.iter().chain(std::iter::repeat("")).take(columns).collect()
+}
+
+/// Records of several inputs read one after the other as a single CSV. The
+/// inputs are opened when reached, and must have the same headers as the
+/// first one, or the same number of columns without headers
+pub struct Records<'a> {
+    options: &'a ReaderOptions,
+    first_path: String,
+    headers: StringRecord,
+    paths: VecDeque<String>,
+    current: RecordIter,
+}
+
+impl<'a> Records<'a> {
+    pub fn new(first: Input, first_path: &str, paths: Vec<String>, options: &'a ReaderOptions) -> Self {
+        Self {
+            options,
+            first_path: String::from(first_path),
+            headers: first.headers,
+            paths: VecDeque::from(paths),
+            current: first.records,
+        }
+    }
+
+    fn open_next(&self, path: &str) -> Result<RecordIter, Box<dyn Error>> {
+        let input = open_input(Some(path), self.options)?;
+        let matching = match self.options.has_headers {
+            true => input.headers == self.headers,
+            false => input.headers.len() == self.headers.len(),
+        };
+        if !matching {
+            return Err(format!("The headers of '{}' don't match the ones of '{}'", path, self.first_path).into());
+        }
+        Ok(input.records)
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<StringRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.current.next() {
+                return Some(result);
+            }
+            let path = self.paths.pop_front()?;
+            match self.open_next(&path) {
+                Ok(records) => self.current = records,
+                Err(err) => {
+                    self.paths.clear();
+                    return Some(Err(err));
+                },
+            }
+        }
+    }
+}
+
+/// Whether the input is downloaded instead of opened as a file
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Prefix of the S3 objects, read with the s3 feature
+pub const S3_PREFIX: &str = "s3://";
+
+/// Bucket and key of an s3://bucket/key URL
+fn split_s3_url(url: &str) -> Option<(&str, &str)> {
+    url.strip_prefix(S3_PREFIX)?.split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+}
+
+fn open_s3(url: &str) -> io::Result<Box<dyn Read>> {
+    let (bucket, key) = split_s3_url(url)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Expected s3://bucket/key, got '{}'", url)))?;
+    open_s3_object(bucket, key)
+}
+
+#[cfg(feature = "s3")]
+fn open_s3_object(bucket: &str, key: &str) -> io::Result<Box<dyn Read>> {
+    Ok(Box::new(s3::open(bucket, key)?))
+}
+
+#[cfg(not(feature = "s3"))]
+fn open_s3_object(_bucket: &str, _key: &str) -> io::Result<Box<dyn Read>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "s3:// inputs need csvpeek built with --features s3"))
+}
+
+/// URLs without their query string and fragment, which don't belong to the
+/// extension
+fn without_query(path: &str) -> &str {
+    match is_url(path) {
+        true => path.split(['?', '#']).next().unwrap_or(path),
+        false => path,
+    }
+}
+
+/// Extension of the file, without the one of the compression
+fn extension(path: Option<&str>) -> Option<&str> {
+    let path = without_query(path?);
+    let path = match Compression::from_path(path) {
+        Compression::None => path,
+        _ => path.rsplit_once('.').map_or(path, |(path, _)| path),
+    };
+    path.rsplit_once('.').map(|(_, extension)| extension)
+}
+
+/// Delimiter used without --delimiter: tabs for .tsv and .tab files, also
+/// when compressed, and commas otherwise
+pub fn default_delimiter(path: Option<&str>) -> u8 {
+    match extension(path) {
+        Some("tsv" | "tab") => b'\t',
+        _ => b',',
+    }
+}
+
+/// Whether the delimiter is guessed without --delimiter: for stdin and files
+/// that are not .csv, .tsv or .tab
+pub fn has_ambiguous_extension(path: Option<&str>) -> bool {
+    !matches!(extension(path), Some("csv" | "tsv" | "tab"))
+}
+
+/// Bytes read from the start of the input to guess its dialect
+pub const SNIFF_SIZE: usize = 8 * 1024;
+
+/// Delimiter and quote of a CSV file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+}
+
+/// Guesses the dialect of a sample of the input: the delimiter that splits
+/// the most lines into the same number of fields, and the quote found
+/// starting the most fields. Falls back to the default delimiter when no
+/// candidate splits the lines
+pub fn sniff(sample: &[u8], default_delimiter: u8) -> Dialect {
+    // The last line of a full sample may be cut
+    let sample = match sample.len() >= SNIFF_SIZE {
+        true => sample.iter().rposition(|&byte| byte == b'\n').map_or(sample, |end| &sample[..=end]),
+        false => sample,
+    };
+    let mut best = (Dialect { delimiter: default_delimiter, quote: guess_quote(sample, default_delimiter) }, 0, 0);
+    for delimiter in [b',', b';', b'\t', b'|', b':'] {
+        let quote = guess_quote(sample, delimiter);
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .quote(quote)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(sample);
+        let field_counts: Vec<usize> = reader.records().map_while(Result::ok).map(|record| record.len()).collect();
+        let Some(&fields) = field_counts.first().filter(|&&fields| fields > 1) else {
+            continue;
+        };
+        let consistent_lines = field_counts.iter().filter(|&&count| count == fields).count();
+        if (consistent_lines, fields) > (best.1, best.2) {
+            best = (Dialect { delimiter, quote }, consistent_lines, fields);
+        }
+    }
+    best.0
+}
+
+/// Single quotes when more fields start with them than with double quotes
+fn guess_quote(sample: &[u8], delimiter: u8) -> u8 {
+    let starting_fields = |quote: u8| (0..sample.len())
+        .filter(|&i| sample[i] == quote && (i == 0 || sample[i - 1] == delimiter || sample[i - 1] == b'\n'))
+        .count();
+    match starting_fields(b'\'') > starting_fields(b'"') {
+        true => b'\'',
+        false => b'"',
+    }
+}
+
+/// Input after peek, the sample followed by the rest
+pub type Peeked<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Reads the sample used by sniff, returning it along with an input that
+/// still yields every byte
+pub fn peek<R: Read>(mut input: R) -> io::Result<(Vec<u8>, Peeked<R>)> {
+    let mut sample = Vec::new();
+    input.by_ref().take(SNIFF_SIZE as u64).read_to_end(&mut sample)?;
+    Ok((sample.clone(), io::Cursor::new(sample).chain(input)))
+}
+
+/// Opens the input, a file path, an HTTP(S) or S3 URL, or stdin for "-" and None.
+/// Downloads are streamed, and compressed inputs are decompressed as they
+/// are read, by default by the file extension
+pub fn open(path: Option<&str>, compression: Option<Compression>) -> io::Result<Box<dyn Read>> {
+    let input: Box<dyn Read> = match path {
+        None | Some(STDIN_PATH) => Box::new(io::stdin()),
+        Some(url) if is_url(url) => Box::new(ureq::get(url).call().map_err(io::Error::other)?.into_body().into_reader()),
+        Some(url) if url.starts_with(S3_PREFIX) => open_s3(url)?,
+        Some(path) => Box::new(File::open(path)?),
+    };
+    let compression = compression.unwrap_or_else(|| path.map_or(Compression::None, Compression::from_path));
+    // The decoders also read inputs made of several compressed streams, like
+    // the ones written by concatenating compressed files
+    let input = BufReader::new(input);
+    Ok(match compression {
+        Compression::None => Box::new(input),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+        Compression::Zstd => Box::new(zstd::Decoder::with_buffer(input)?),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(input)),
+        Compression::Xz => Box::new(liblzma::bufread::XzDecoder::new_multi_decoder(input)),
+    })
+}
+
+/// Time waited at the end of a followed input before reading it again
+const FOLLOW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// File read past its end, waiting for what gets appended to it
+struct Follow {
+    file: File,
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            std::thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+/// Opens the input to never reach its end, for --follow. Only uncompressed
+/// local files are followed, and stdin, which already waits for more input
+fn follow(path: Option<&str>, compression: Option<Compression>) -> io::Result<Box<dyn Read>> {
+    let path = match path {
+        None | Some(STDIN_PATH) => return open(path, compression),
+        Some(path) => path,
+    };
+    let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+    if is_url(path) || path.starts_with(S3_PREFIX) || compression != Compression::None {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("Can't follow '{}', only uncompressed local files", path)));
+    }
+    Ok(Box::new(BufReader::new(Follow { file: File::open(path)? })))
+}
+
+/// Parses an --encoding label, like "latin1", "windows-1252" or "utf-16le"
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| format!("Unknown encoding '{}'", label))
+}
+
+/// Transcodes the input to UTF-8 as it is read. A byte order mark takes
+/// precedence over the given encoding
+pub fn decode(input: Box<dyn Read>, encoding: Option<&'static Encoding>) -> Box<dyn Read> {
+    match encoding {
+        Some(encoding) => Box::new(DecodeReaderBytesBuilder::new().encoding(Some(encoding)).build(input)),
+        None => input,
+    }
+}
+
+/// Drops the first lines of the input, for preambles before the header
+pub fn skip_lines(input: Box<dyn Read>, lines: usize) -> io::Result<Box<dyn Read>> {
+    if lines == 0 {
+        return Ok(input);
+    }
+    let mut input = BufReader::new(input);
+    let mut line = Vec::new();
+    for _ in 0..lines {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+    }
+    Ok(Box::new(input))
+}
+
+/// Parses the single byte of --delimiter and --comment. "\t" is accepted
+/// for tabs, which are hard to type in a shell
+pub fn parse_byte(value: &str) -> Result<u8, String> {
+    match value {
+        "\\t" => Ok(b'\t'),
+        _ if value.len() == 1 => Ok(value.as_bytes()[0]),
+        _ => Err(format!("Expected a single byte, got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+fn read_compressed(extension: &str, compressed: &[u8]) -> String {
+    let path = std::env::temp_dir().join(format!("csvpeek-input-{}.csv.{}", std::process::id(), extension));
+    std::fs::write(&path, compressed).unwrap();
+    let mut content = String::new();
+    open(path.to_str(), None).unwrap().read_to_string(&mut content).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    content
+}
+
+/// Input with the given header and rows, for the tests of the modules
+/// reading other files
+#[cfg(test)]
+pub fn test_input(rows: &[&[&str]]) -> Input {
+    let records: Vec<StringRecord> = rows[1..].iter().map(|&row| StringRecord::from(row)).collect();
+    Input {
+        headers: StringRecord::from(rows[0]),
+        records: Box::new(records.into_iter().map(Ok)),
+        sniffed: None,
+    }
+}
+
+/// Options of a plain CSV file with a header, for the tests reading files
+#[cfg(test)]
+pub fn test_options() -> ReaderOptions {
+    ReaderOptions {
+        format: None,
+        compression: None,
+        encoding: None,
+        skip_lines: 0,
+        delimiter: None,
+        sniff: false,
+        has_headers: true,
+        comment: None,
+        follow: false,
+        flexible: false,
+        sheet: None,
+        fwf: None,
+    }
+}
+
+#[test]
+fn test_open_compressed() {
+    use std::io::Write;
+
+    let csv = b"a,b\n1,2\n";
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(csv).unwrap();
+    assert_eq!(read_compressed("gz", &gzip.finish().unwrap()), "a,b\n1,2\n");
+
+    assert_eq!(read_compressed("zst", &zstd::encode_all(&csv[..], 0).unwrap()), "a,b\n1,2\n");
+
+    let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    bzip2.write_all(csv).unwrap();
+    assert_eq!(read_compressed("bz2", &bzip2.finish().unwrap()), "a,b\n1,2\n");
+
+    let mut xz = liblzma::write::XzEncoder::new(Vec::new(), 6);
+    xz.write_all(csv).unwrap();
+    assert_eq!(read_compressed("xz", &xz.finish().unwrap()), "a,b\n1,2\n");
+
+    assert_eq!(Compression::from_path("data.csv"), Compression::None);
+}
+
+#[test]
+fn test_parse_byte() {
+    assert_eq!(parse_byte(";"), Ok(b';'));
+    assert_eq!(parse_byte("\\t"), Ok(b'\t'));
+    assert!(parse_byte("ab").is_err());
+    assert!(parse_byte("").is_err());
+}
+
+#[test]
+fn test_skip_lines() {
+    let mut content = String::new();
+    skip_lines(Box::new(&b"Exported 2024-01-01\r\nAccount 1\na,b\n1,2\n"[..]), 2).unwrap()
+        .read_to_string(&mut content).unwrap();
+    assert_eq!(content, "a,b\n1,2\n");
+
+    content.clear();
+    skip_lines(Box::new(&b"a,b\n"[..]), 5).unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, "");
+}
+
+#[test]
+fn test_decode() {
+    let mut content = String::new();
+    decode(Box::new(&b"name\nJos\xe9\n"[..]), Some(parse_encoding("latin1").unwrap()))
+        .read_to_string(&mut content).unwrap();
+    assert_eq!(content, "name\nJosé\n");
+
+    content.clear();
+    let utf16: Vec<u8> = "a,b\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    decode(Box::new(std::io::Cursor::new(utf16)), Some(parse_encoding("utf-16le").unwrap()))
+        .read_to_string(&mut content).unwrap();
+    assert_eq!(content, "a,b\n");
+    assert!(parse_encoding("klingon").is_err());
+}
+
+#[test]
+fn test_default_delimiter() {
+    assert_eq!(default_delimiter(Some("results.tsv")), b'\t');
+    assert_eq!(default_delimiter(Some("results.tab.gz")), b'\t');
+    assert_eq!(default_delimiter(Some("results.csv")), b',');
+    assert_eq!(default_delimiter(Some("tsv")), b',');
+    assert_eq!(default_delimiter(None), b',');
+}
+
+#[test]
+fn test_sniff() {
+    let comma = Dialect { delimiter: b',', quote: b'"' };
+    assert_eq!(sniff(b"a;b;c\n1;\"x,y\";3\n4;5;6\n", b','), Dialect { delimiter: b';', quote: b'"' });
+    assert_eq!(sniff(b"a\tb\n1\t2\n", b','), Dialect { delimiter: b'\t', quote: b'"' });
+    assert_eq!(sniff(b"a|b\n'x|y'|2\n", b','), Dialect { delimiter: b'|', quote: b'\'' });
+    assert_eq!(sniff(b"a,b\n1,2\n", b','), comma);
+    // A single column keeps the default
+    assert_eq!(sniff(b"name\nRome\n", b','), comma);
+
+    let (sample, mut input) = peek(&b"a;b\n1;2\n"[..]).unwrap();
+    let mut content = String::new();
+    input.read_to_string(&mut content).unwrap();
+    assert_eq!(sample, b"a;b\n1;2\n");
+    assert_eq!(content, "a;b\n1;2\n");
+    assert!(has_ambiguous_extension(Some("export.txt")));
+    assert!(!has_ambiguous_extension(Some("export.csv.gz")));
+}
+
+#[test]
+fn test_flexible() {
+    assert_eq!(fit(StringRecord::from(vec!["1"]), 3), StringRecord::from(vec!["1", "", ""]));
+    assert_eq!(fit(StringRecord::from(vec!["1", "2", "3"]), 2), StringRecord::from(vec!["1", "2"]));
+
+    let path = std::env::temp_dir().join(format!("csvpeek-flexible-{}.csv", std::process::id()));
+    std::fs::write(&path, "a,b\n1\n2,3,4\n").unwrap();
+    let mut options = ReaderOptions { flexible: true, ..test_options() };
+    let records: Vec<StringRecord> = open_input(path.to_str(), &options).unwrap().records.map(Result::unwrap).collect();
+    assert_eq!(records, vec![StringRecord::from(vec!["1", ""]), StringRecord::from(vec!["2", "3"])]);
+    options.flexible = false;
+    assert!(open_input(path.to_str(), &options).unwrap().records.next().unwrap().is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_follow() {
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("csvpeek-follow-{}.csv", std::process::id()));
+    std::fs::write(&path, "a,b\n1,2\n").unwrap();
+    let mut input = BufReader::new(follow(path.to_str(), None).unwrap());
+    let mut line = String::new();
+    input.read_line(&mut line).unwrap();
+    input.read_line(&mut line).unwrap();
+    assert_eq!(line, "a,b\n1,2\n");
+
+    let appended = path.clone();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(FOLLOW_INTERVAL);
+        let mut file = std::fs::OpenOptions::new().append(true).open(appended).unwrap();
+        file.write_all(b"3,4\n").unwrap();
+    });
+    line.clear();
+    input.read_line(&mut line).unwrap();
+    assert_eq!(line, "3,4\n");
+    writer.join().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(follow(Some("logs.csv.gz"), None).is_err());
+}
+
+#[test]
+fn test_records_of_several_inputs() {
+    let dir = std::env::temp_dir();
+    let write = |name: &str, content: &str| {
+        let path = dir.join(format!("csvpeek-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        String::from(path.to_str().unwrap())
+    };
+    let jan = write("jan.csv", "id,status\n1,ok\n");
+    let feb = write("feb.csv", "id,status\n2,error\n3,ok\n");
+    let other = write("other.csv", "id,state\n4,ok\n");
+    let options = test_options();
+
+    let records = Records::new(open_input(Some(&jan), &options).unwrap(), &jan, vec![feb.clone()], &options);
+    let ids: Vec<String> = records.map(|record| String::from(&record.unwrap()[0])).collect();
+    assert_eq!(ids, vec!["1", "2", "3"]);
+
+    let mut records = Records::new(open_input(Some(&jan), &options).unwrap(), &jan, vec![other.clone(), feb.clone()], &options);
+    assert!(records.next().unwrap().is_ok());
+    assert!(records.next().unwrap().is_err());
+    assert!(records.next().is_none());
+    for path in [jan, feb, other] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_expand_globs() {
+    let dir = std::env::temp_dir().join(format!("csvpeek-glob-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["2024-02.csv", "2024-01.csv", "2023-12.csv"] {
+        std::fs::write(dir.join(name), "a\n").unwrap();
+    }
+    let dir_str = dir.to_str().unwrap();
+
+    let expanded = expand_globs(&[format!("{}/2024-*.csv", dir_str), String::from("-")]).unwrap();
+    assert_eq!(expanded, vec![format!("{}/2024-01.csv", dir_str), format!("{}/2024-02.csv", dir_str), String::from("-")]);
+    assert!(expand_globs(&[format!("{}/2025-*.csv", dir_str)]).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_open_url() {
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    // Serves a single response, enough for one download
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\na,b\n1,2\n").unwrap();
+    });
+
+    let url = format!("http://127.0.0.1:{}/data.csv?token=x", port);
+    assert_eq!(extension(Some(&url)), Some("csv"));
+    let mut content = String::new();
+    open(Some(&url), None).unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, "a,b\n1,2\n");
+    server.join().unwrap();
+}
+
+#[test]
+fn test_split_s3_url() {
+    assert_eq!(split_s3_url("s3://exports/2024/sales.csv.gz"), Some(("exports", "2024/sales.csv.gz")));
+    assert_eq!(split_s3_url("s3://exports/"), None);
+    assert_eq!(split_s3_url("s3://exports"), None);
+    assert_eq!(Compression::from_path("s3://exports/sales.csv.zst"), Compression::Zstd);
+}
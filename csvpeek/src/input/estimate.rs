@@ -0,0 +1,67 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use super::ReaderOptions;
+
+/// Rows of a file estimated from its first bytes, for --info --estimate
+#[derive(Debug, PartialEq)]
+pub struct RowEstimate {
+    /// Non-empty lines in the bytes read. A quoted field with newlines
+    /// counts as several, so there can be fewer records than this
+    pub sampled_rows: usize,
+    pub rows: u64,
+    /// Whether the whole file was read, so the rows were counted
+    pub exact: bool,
+}
+
+/// Estimates the rows of a plain CSV file from the rows in its first bytes
+/// and its size, without reading the rest. None when it isn't a plain file,
+/// like for stdin, compressed or encoded inputs, the same as for --tail
+pub fn estimate_rows(path: Option<&str>, sample_size: u64, options: &ReaderOptions) -> Result<Option<RowEstimate>, Box<dyn Error>> {
+    let Some(path) = path.filter(|&path| super::tail::is_seekable(path, options)) else {
+        return Ok(None);
+    };
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut sample = Vec::new();
+    file.take(sample_size).read_to_end(&mut sample)?;
+    Ok(Some(estimate(&sample, size, options.skip_lines + usize::from(options.has_headers))?))
+}
+
+/// The rows are the non-empty lines after the skipped ones, so a quoted
+/// field with newlines counts as several. The last line of the sample may
+/// be cut, so it only counts when the whole file was read. Without a whole
+/// line there's nothing to estimate from
+fn estimate(sample: &[u8], size: u64, skipped_lines: usize) -> Result<RowEstimate, String> {
+    let exact = sample.len() as u64 == size;
+    let (mut skipped_bytes, mut row_bytes, mut rows) = (0, 0, 0);
+    for (i, line) in sample.split_inclusive(|&byte| byte == b'\n').enumerate() {
+        if i < skipped_lines {
+            skipped_bytes += line.len();
+            continue;
+        }
+        if !exact && !line.ends_with(b"\n") {
+            break;
+        }
+        row_bytes += line.len();
+        if !line.trim_ascii().is_empty() {
+            rows += 1;
+        }
+    }
+    let estimated_rows = match (exact, rows) {
+        (true, _) => rows as u64,
+        (false, 0) => return Err(format!("--estimate sample too small, no whole row in its first {} bytes", sample.len())),
+        (false, _) => ((size - skipped_bytes as u64) as f64 * rows as f64 / row_bytes as f64).round() as u64,
+    };
+    Ok(RowEstimate { sampled_rows: rows, rows: estimated_rows, exact })
+}
+
+#[test]
+fn test_estimate() {
+    let content = b"id,name\n1,ab\n2,cd\n\n3,ef\n4,gh\n";
+    assert_eq!(estimate(content, content.len() as u64, 1), Ok(RowEstimate { sampled_rows: 4, rows: 4, exact: true }));
+    // 3 rows of 5 bytes, and a cut one, in the first 24 bytes of 1008
+    assert_eq!(estimate(b"id,name\n1,ab\n2,cd\n3,ef\n4,", 1008, 1), Ok(RowEstimate { sampled_rows: 3, rows: 200, exact: false }));
+    assert!(estimate(b"id,name\n1,a", 1000, 1).is_err());
+    assert_eq!(estimate(b"id,name\n", 8, 1), Ok(RowEstimate { sampled_rows: 0, rows: 0, exact: true }));
+}
@@ -0,0 +1,108 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+use csv::StringRecord;
+use super::Input;
+
+/// Columns of a fixed-width file, from --fwf
+#[derive(Debug, Clone, PartialEq)]
+pub struct FwfSpec {
+    /// Names given by a spec file, used as the header
+    pub names: Option<Vec<String>>,
+    /// 0-based character positions of each column, both included
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// Parses --fwf: ranges like "0-9,10-24", or the path of a file with a
+/// column per line, either "10-24" or "name 10-24"
+pub fn parse_fwf(value: &str) -> Result<FwfSpec, String> {
+    let path = std::path::Path::new(value);
+    if !path.is_file() {
+        let ranges = value.split(',').map(parse_range).collect::<Result<_, _>>()?;
+        return Ok(FwfSpec { names: None, ranges });
+    }
+    let content = std::fs::read_to_string(path).map_err(|err| format!("Can't read '{}': {}", value, err))?;
+    let mut names = Vec::new();
+    let mut ranges = Vec::new();
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match line.rsplit_once(char::is_whitespace) {
+            Some((name, range)) => {
+                names.push(String::from(name.trim()));
+                ranges.push(parse_range(range)?);
+            },
+            None => ranges.push(parse_range(line)?),
+        }
+    }
+    let names = match names.len() {
+        0 => None,
+        n if n == ranges.len() => Some(names),
+        _ => return Err(format!("Either all the columns of '{}' have a name or none", value)),
+    };
+    Ok(FwfSpec { names, ranges })
+}
+
+fn parse_range(range: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Expected a range like 0-9, got '{}'", range);
+    let (start, end) = range.trim().split_once('-').ok_or_else(invalid)?;
+    let start = start.trim().parse().map_err(|_| invalid())?;
+    let end = end.trim().parse().map_err(|_| invalid())?;
+    match start <= end {
+        true => Ok((start, end)),
+        false => Err(invalid()),
+    }
+}
+
+/// Reads a fixed-width text file, slicing each line by the characters of the
+/// columns and trimming the fields. The header is the first line, unless the
+/// spec names the columns or has_headers is unset
+pub fn open(input: Box<dyn Read>, spec: &FwfSpec, has_headers: bool) -> Result<Input, Box<dyn Error>> {
+    let ranges = spec.ranges.clone();
+    let mut records = BufReader::new(input).lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(move |line| -> Result<StringRecord, Box<dyn Error>> { Ok(slice(&line?, &ranges)) })
+        .peekable();
+    let headers = match &spec.names {
+        Some(names) => StringRecord::from(names.clone()),
+        None if has_headers => records.next().transpose()?.unwrap_or_default(),
+        None => match records.peek() {
+            Some(Ok(first)) => first.clone(),
+            _ => StringRecord::new(),
+        },
+    };
+    Ok(Input { headers, records: Box::new(records), sniffed: None })
+}
+
+fn slice(line: &str, ranges: &[(usize, usize)]) -> StringRecord {
+    let chars: Vec<char> = line.chars().collect();
+    ranges.iter()
+        .map(|&(start, end)| {
+            let end = (end + 1).min(chars.len());
+            chars.get(start..end).map_or(String::new(), |field| field.iter().collect::<String>().trim().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_fwf() {
+    let spec = parse_fwf("0-4, 5-11,12-15").unwrap();
+    assert_eq!(spec.ranges, vec![(0, 4), (5, 11), (12, 15)]);
+    assert!(parse_fwf("0-4,9-5").is_err());
+    assert!(parse_fwf("0:4").is_err());
+
+    let text = "id   name   qty\n001  Ann    12\n\n002  Renée\n";
+    let input = open(Box::new(text.as_bytes()), &spec, true).unwrap();
+    assert_eq!(input.headers, vec!["id", "name", "qty"]);
+    let records: Vec<StringRecord> = input.records.map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+        StringRecord::from(vec!["001", "Ann", "12"]),
+        StringRecord::from(vec!["002", "Renée", ""]),
+    ]);
+
+    let path = std::env::temp_dir().join(format!("csvpeek-fwf-{}.txt", std::process::id()));
+    std::fs::write(&path, "id 0-4\nname 5-11\n").unwrap();
+    let spec = parse_fwf(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(spec.names, Some(vec![String::from("id"), String::from("name")]));
+    let input = open(Box::new(&b"001  Ann\n"[..]), &spec, true).unwrap();
+    assert_eq!(input.headers, vec!["id", "name"]);
+    assert_eq!(input.records.count(), 1);
+}
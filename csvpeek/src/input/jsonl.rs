@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use super::Input;
+
+/// Lines read up front to find out the columns. Keys first seen after them
+/// are ignored
+const SAMPLE_LINES: usize = 1000;
+
+type Object = Map<String, Value>;
+
+/// Reads JSON Lines, one object per line, as rows with a column for each top
+/// level key, in the order they are first seen in the sampled lines. Blank
+/// lines are skipped
+pub fn open(input: Box<dyn Read>) -> Result<Input, Box<dyn Error>> {
+    let mut objects = BufReader::new(input).lines().enumerate()
+        .filter_map(|(i, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some(parse_object(i + 1, &line)),
+            Err(err) => Some(Err(err.into())),
+        });
+    let sample = objects.by_ref().take(SAMPLE_LINES).collect::<Result<Vec<Object>, _>>()?;
+
+    let mut keys = Vec::new();
+    let mut seen = HashSet::new();
+    for key in sample.iter().flat_map(Map::keys) {
+        if seen.insert(key) {
+            keys.push(key.clone());
+        }
+    }
+    let headers = StringRecord::from(keys.clone());
+    let records = sample.into_iter().map(Ok).chain(objects)
+        .map(move |object| object.map(|object| to_record(&object, &keys)));
+    Ok(Input { headers, records: Box::new(records), sniffed: None })
+}
+
+fn parse_object(line_number: usize, line: &str) -> Result<Object, Box<dyn Error>> {
+    match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(format!("Line {}: expected a JSON object", line_number).into()),
+        Err(err) => Err(format!("Line {}: {}", line_number, err).into()),
+    }
+}
+
+/// Fields of an object: strings as they are, nothing for nulls and missing
+/// keys, and the JSON text of numbers, booleans, arrays and nested objects
+fn to_record(object: &Object, keys: &[String]) -> StringRecord {
+    keys.iter()
+        .map(|key| match object.get(key) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(text)) => text.clone(),
+            Some(value) => value.to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_open_jsonl() {
+    let jsonl = b"{\"id\":1,\"name\":\"Ann\"}\n\n{\"id\":2,\"tags\":[\"a\"],\"ok\":true,\"name\":null}\n";
+    let input = open(Box::new(&jsonl[..])).unwrap();
+    assert_eq!(input.headers, vec!["id", "name", "tags", "ok"]);
+    let records: Vec<StringRecord> = input.records.map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+        StringRecord::from(vec!["1", "Ann", "", ""]),
+        StringRecord::from(vec!["2", "", "[\"a\"]", "true"]),
+    ]);
+
+    assert!(open(Box::new(&b"{\"id\":1}\n[1,2]\n"[..])).is_err());
+}
@@ -0,0 +1,80 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_cast::display::{ArrayFormatter, FormatOptions};
+use arrow_schema::ArrowError;
+use csv::StringRecord;
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use parquet::file::reader::ChunkReader;
+use super::{Compression, Input, STDIN_PATH};
+
+/// Reads a parquet file one batch of rows at a time, with its values
+/// formatted as text and nothing for nulls. Parquet needs random access, so
+/// downloads, stdin and compressed files are read into memory first
+pub fn open(path: Option<&str>, compression: Option<Compression>) -> Result<Input, Box<dyn Error>> {
+    let reader = match path {
+        Some(file) if is_plain_file(file, compression) => batches(File::open(file)?)?,
+        _ => {
+            let mut content = Vec::new();
+            super::open(path, compression)?.read_to_end(&mut content)?;
+            batches(bytes::Bytes::from(content))?
+        },
+    };
+    let headers = reader.schema().fields().iter().map(|field| field.name().as_str()).collect();
+    let records = reader
+        .map(|batch| batch.and_then(|batch| rows(&batch)))
+        .flat_map(|rows| match rows {
+            Ok(rows) => rows.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err.into())],
+        });
+    Ok(Input { headers, records: Box::new(records), sniffed: None })
+}
+
+fn is_plain_file(path: &str, compression: Option<Compression>) -> bool {
+    let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+    path != STDIN_PATH && !super::is_url(path) && !path.starts_with(super::S3_PREFIX) && compression == Compression::None
+}
+
+fn batches<T: ChunkReader + 'static>(input: T) -> Result<ParquetRecordBatchReader, Box<dyn Error>> {
+    Ok(ParquetRecordBatchReaderBuilder::try_new(input)?.build()?)
+}
+
+fn rows(batch: &RecordBatch) -> Result<Vec<StringRecord>, ArrowError> {
+    let options = FormatOptions::default();
+    let columns = batch.columns().iter()
+        .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+        .collect::<Result<Vec<ArrayFormatter>, _>>()?;
+    Ok((0..batch.num_rows())
+        .map(|row| columns.iter().map(|column| column.value(row).to_string()).collect())
+        .collect())
+}
+
+#[test]
+fn test_open_parquet() {
+    use std::sync::Arc;
+    use arrow_array::{Float64Array, Int64Array, StringArray};
+
+    let batch = RecordBatch::try_from_iter([
+        ("id", Arc::new(Int64Array::from(vec![1, 2])) as _),
+        ("price", Arc::new(Float64Array::from(vec![Some(9.5), None])) as _),
+        ("name", Arc::new(StringArray::from(vec!["pen", "ink"])) as _),
+    ]).unwrap();
+    let mut writer = parquet::arrow::ArrowWriter::try_new(Vec::new(), batch.schema(), None).unwrap();
+    writer.write(&batch).unwrap();
+    let content = writer.into_inner().unwrap();
+
+    let path = std::env::temp_dir().join(format!("csvpeek-input-{}.parquet", std::process::id()));
+    std::fs::write(&path, &content).unwrap();
+    let input = open(path.to_str(), None).unwrap();
+    assert_eq!(input.headers, vec!["id", "price", "name"]);
+    let records: Vec<StringRecord> = input.records.map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+        StringRecord::from(vec!["1", "9.5", "pen"]),
+        StringRecord::from(vec!["2", "", "ink"]),
+    ]);
+    std::fs::remove_file(&path).unwrap();
+
+    let input = batches(bytes::Bytes::from(content)).unwrap();
+    assert_eq!(input.map(|batch| batch.unwrap().num_rows()).sum::<usize>(), 2);
+}
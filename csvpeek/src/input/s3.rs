@@ -0,0 +1,35 @@
+use std::io::{self, Read};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::Client;
+use tokio::io::AsyncRead;
+use tokio::runtime::Runtime;
+use tokio_util::io::SyncIoBridge;
+
+/// Body of an S3 object, streamed as it is read. The runtime of the SDK is
+/// kept as long as the body, which is dropped first
+pub struct S3Reader {
+    body: SyncIoBridge<Box<dyn AsyncRead + Unpin + Send>>,
+    _runtime: Runtime,
+}
+
+/// Starts the GET of an object, with the credentials and region of the
+/// usual AWS environment variables and config files
+pub fn open(bucket: &str, key: &str) -> io::Result<S3Reader> {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+    let object = runtime.block_on(async {
+        let config = aws_config::load_from_env().await;
+        Client::new(&config).get_object().bucket(bucket).key(key).send().await
+    });
+    let object = object.map_err(|err| io::Error::other(DisplayErrorContext(err).to_string()))?;
+    let body: Box<dyn AsyncRead + Unpin + Send> = Box::new(object.body.into_async_read());
+    Ok(S3Reader {
+        body: SyncIoBridge::new_with_handle(body, runtime.handle().clone()),
+        _runtime: runtime,
+    })
+}
+
+impl Read for S3Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
@@ -0,0 +1,128 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use super::{fit, Compression, Dialect, InputFormat, ReaderOptions, RecordIter, STDIN_PATH};
+
+/// Bytes read at a time from the end of the file
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Last rows of a plain CSV file, read backwards from its end instead of
+/// scanning the whole file. None when that's not possible: for stdin,
+/// downloads, compressed or encoded inputs, when the last lines may be part
+/// of quoted fields with newlines, and when the file doesn't have more lines
+/// than the rows asked for, the skipped lines and the header
+pub fn tail(path: Option<&str>, dialect: Dialect, columns: usize, rows: usize,
+            options: &ReaderOptions) -> Result<Option<RecordIter>, Box<dyn Error>> {
+    let Some(path) = path.filter(|&path| is_seekable(path, options)) else {
+        return Ok(None);
+    };
+    let before = options.skip_lines + usize::from(options.has_headers);
+    let Some(content) = last_lines(File::open(path)?, rows, before)? else {
+        return Ok(None);
+    };
+    // A quoted field with a newline leaves an odd number of quotes in the
+    // lines it spans, and then the lines can't be told apart from rows
+    if content.split(|&byte| byte == b'\n').any(|line| line.iter().filter(|&&byte| byte == dialect.quote).count() % 2 == 1) {
+        return Ok(None);
+    }
+    let reader = csv::ReaderBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(io::Cursor::new(content));
+    let flexible = options.flexible;
+    let records = reader.into_records().map(move |result| match result {
+        Ok(record) if record.len() != columns && flexible => Ok(fit(record, columns)),
+        Ok(record) if record.len() != columns => {
+            Err(format!("Found a row with {} fields, but the header has {}", record.len(), columns).into())
+        },
+        result => result.map_err(Box::from),
+    });
+    Ok(Some(Box::new(records)))
+}
+
+/// Whether the input is a plain CSV file, which can be read from anywhere
+pub fn is_seekable(path: &str, options: &ReaderOptions) -> bool {
+    let compression = options.compression.unwrap_or_else(|| Compression::from_path(path));
+    let format = options.format.unwrap_or_else(|| InputFormat::from_path(Some(path)));
+    path != STDIN_PATH && !super::is_url(path) && !path.starts_with(super::S3_PREFIX)
+        && compression == Compression::None && format == InputFormat::Csv
+        && options.encoding.is_none() && options.comment.is_none() && options.fwf.is_none() && !options.follow
+}
+
+/// Bytes of the last non-empty lines of the input, when at least `before`
+/// lines, and always one, come before them
+fn last_lines<R: Read + Seek>(mut input: R, lines: usize, before: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut position = input.seek(SeekFrom::End(0))?;
+    let mut content = Vec::new();
+    while position > 0 {
+        let size = BLOCK_SIZE.min(position);
+        position -= size;
+        input.seek(SeekFrom::Start(position))?;
+        let mut block = vec![0; size as usize];
+        input.read_exact(&mut block)?;
+        block.extend_from_slice(&content);
+        content = block;
+        let preceded = start_of_last_lines(&content, lines + before.max(1) - 1);
+        if let Some(start) = preceded.and_then(|_| start_of_last_lines(&content, lines)) {
+            return Ok(Some(content.split_off(start)));
+        }
+    }
+    Ok(None)
+}
+
+/// Position where the last non-empty lines start, if a line break is found
+/// before them
+fn start_of_last_lines(content: &[u8], lines: usize) -> Option<usize> {
+    let mut start = content.len();
+    let mut found = 0;
+    while found < lines {
+        let line_break = content[..start.saturating_sub(1)].iter().rposition(|&byte| byte == b'\n')?;
+        if content[line_break + 1..start].iter().any(|&byte| !matches!(byte, b'\r' | b'\n')) {
+            found += 1;
+        }
+        start = line_break + 1;
+    }
+    Some(start)
+}
+
+#[test]
+fn test_last_lines() {
+    let content = b"a,b\n1,2\n\n3,4\r\n5,6";
+    assert_eq!(last_lines(io::Cursor::new(&content[..]), 2, 1).unwrap(), Some(b"3,4\r\n5,6".to_vec()));
+    assert_eq!(last_lines(io::Cursor::new(&b"a,b\n1,2\n3,4\n"[..]), 1, 1).unwrap(), Some(b"3,4\n".to_vec()));
+    // Without a line before them, the last lines could include the header
+    assert_eq!(last_lines(io::Cursor::new(&b"a,b\n1,2\n"[..]), 2, 1).unwrap(), None);
+    assert_eq!(last_lines(io::Cursor::new(&b"a,b\n1,2\n"[..]), 2, 0).unwrap(), None);
+    // Nor could the skipped lines be among them
+    let content = b"Exported today\nAccount 1\nid,v\n1,a\n2,b\n";
+    assert_eq!(last_lines(io::Cursor::new(&content[..]), 3, 3).unwrap(), None);
+    assert_eq!(last_lines(io::Cursor::new(&content[..]), 2, 3).unwrap(), Some(b"1,a\n2,b\n".to_vec()));
+    assert_eq!(start_of_last_lines(b"a,b\n1,2\n", 0), Some(8));
+}
+
+#[test]
+fn test_tail() {
+    use csv::StringRecord;
+    use super::test_options;
+
+    let path = std::env::temp_dir().join(format!("csvpeek-tail-{}.csv", std::process::id()));
+    let dialect = Dialect { delimiter: b',', quote: b'"' };
+    let options = test_options();
+    let rows = (1..=10000).map(|i| format!("{},\"x, {}\"\n", i, i)).collect::<String>();
+    std::fs::write(&path, format!("id,name\n{}", rows)).unwrap();
+    let records: Vec<StringRecord> = tail(path.to_str(), dialect, 2, 2, &options).unwrap().unwrap().map(Result::unwrap).collect();
+    assert_eq!(records, vec![StringRecord::from(vec!["9999", "x, 9999"]), StringRecord::from(vec!["10000", "x, 10000"])]);
+
+    std::fs::write(&path, "id,note\n1,\"two\nlines\"\n2,ok\n").unwrap();
+    assert!(tail(path.to_str(), dialect, 2, 2, &options).unwrap().is_none());
+    assert!(tail(None, dialect, 2, 2, &options).unwrap().is_none());
+
+    std::fs::write(&path, "Exported today\nAccount 1\nid,v\n1,a\n2,b\n").unwrap();
+    let options = ReaderOptions { skip_lines: 2, ..options };
+    assert!(tail(path.to_str(), dialect, 2, 3, &options).unwrap().is_none());
+    let records: Vec<StringRecord> = tail(path.to_str(), dialect, 2, 2, &options).unwrap().unwrap().map(Result::unwrap).collect();
+    assert_eq!(records, vec![StringRecord::from(vec!["1", "a"]), StringRecord::from(vec!["2", "b"])]);
+    std::fs::remove_file(&path).unwrap();
+}
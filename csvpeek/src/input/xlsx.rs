@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::io::{Cursor, Read};
+use calamine::{Data, DataType, Reader, Xlsx};
+use csv::StringRecord;
+use super::Input;
+
+/// Reads a sheet of an Excel workbook, the first one by default, with its
+/// first row as the header unless has_headers is unset. Dates are written
+/// like "2024-01-31 08:30:00", and whole numbers without decimals
+pub fn open(mut input: Box<dyn Read>, sheet: Option<&str>, has_headers: bool) -> Result<Input, Box<dyn Error>> {
+    // Workbooks are zip files, which are read with random access
+    let mut content = Vec::new();
+    input.read_to_end(&mut content)?;
+    let mut workbook = Xlsx::new(Cursor::new(content))?;
+    let sheets = workbook.sheet_names();
+    let name = match sheet {
+        Some(sheet) if sheets.iter().any(|name| name == sheet) => String::from(sheet),
+        Some(sheet) => return Err(format!("No sheet '{}' in the workbook, its sheets are: {}", sheet, sheets.join(", ")).into()),
+        None => sheets.first().cloned().ok_or("The workbook has no sheets")?,
+    };
+    let range = workbook.worksheet_range(&name)?;
+    let mut rows = range.rows().map(|row| row.iter().map(field).collect::<StringRecord>());
+    let headers = rows.next().unwrap_or_default();
+    let mut records: Vec<StringRecord> = rows.collect();
+    if !has_headers {
+        records.insert(0, headers.clone());
+    }
+    Ok(Input { headers, records: Box::new(records.into_iter().map(Ok)), sniffed: None })
+}
+
+fn field(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) => match cell.as_datetime() {
+            Some(date_time) if date_time.time() == chrono::NaiveTime::MIN => date_time.format("%Y-%m-%d").to_string(),
+            Some(date_time) => date_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => cell.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+#[test]
+fn test_open_xlsx() {
+    let mut workbook = rust_xlsxwriter::Workbook::new();
+    workbook.add_worksheet().set_name("Summary").unwrap().write(0, 0, "total").unwrap();
+    let sheet = workbook.add_worksheet().set_name("Sales").unwrap();
+    sheet.write(0, 0, "id").unwrap();
+    sheet.write(0, 1, "price").unwrap();
+    sheet.write(0, 2, "day").unwrap();
+    sheet.write(1, 0, 1).unwrap();
+    sheet.write(1, 1, 9.5).unwrap();
+    let day = rust_xlsxwriter::ExcelDateTime::from_ymd(2024, 1, 31).unwrap();
+    sheet.write_with_format(1, 2, &day, &rust_xlsxwriter::Format::new().set_num_format("yyyy-mm-dd")).unwrap();
+    sheet.write(2, 0, 2).unwrap();
+    let content = workbook.save_to_buffer().unwrap();
+
+    let input = open(Box::new(Cursor::new(content.clone())), Some("Sales"), true).unwrap();
+    assert_eq!(input.headers, vec!["id", "price", "day"]);
+    let records: Vec<StringRecord> = input.records.map(Result::unwrap).collect();
+    assert_eq!(records, vec![
+        StringRecord::from(vec!["1", "9.5", "2024-01-31"]),
+        StringRecord::from(vec!["2", "", ""]),
+    ]);
+
+    let input = open(Box::new(Cursor::new(content.clone())), None, false).unwrap();
+    assert_eq!(input.records.count(), 1);
+    assert!(open(Box::new(Cursor::new(content)), Some("Costs"), true).is_err());
+}
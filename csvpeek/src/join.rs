@@ -0,0 +1,339 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::path::Path;
+use csv::StringRecord;
+use crate::columns::column_index;
+use crate::input::{IndexedRecord, Input, RecordIter};
+
+/// Parses the key columns of --on like "id" or "id,date", or "customer_id=id"
+/// when the other file names them differently
+fn parse_on(on_str: &str, headers: &StringRecord, other_headers: &StringRecord,
+            path: &str) -> Result<(Vec<usize>, Vec<usize>), String> {
+    let (mut left_keys, mut right_keys) = (Vec::new(), Vec::new());
+    for key_str in on_str.split(',') {
+        let (left_name, right_name) = key_str.split_once('=').unwrap_or((key_str, key_str));
+        left_keys.push(column_index(headers, left_name, "--on")?);
+        right_keys.push(column_index(other_headers, right_name, "--on").map_err(|err| format!("{} of {}", err, path))?);
+    }
+    Ok((left_keys, right_keys))
+}
+
+/// Rows kept by --join, for --join-type
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum JoinType {
+    /// Only the rows with a match in the other file
+    Inner,
+    /// Also the rows without a match, with empty fields for the other file
+    Left,
+    /// Also the rows of the other file without a match, after the rest
+    Right,
+    /// Both the rows of left and right
+    Full,
+}
+
+/// Hash join of the rows with the rows of another file, for --join. The
+/// other file is read into memory by the value of its key columns, and each
+/// row is followed by the columns of the rows with the same key, all but the
+/// key ones. Rows with an empty key don't match any other
+pub struct Join {
+    join_type: JoinType,
+    left_columns: usize,
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    right_columns: Vec<usize>,
+    right_headers: Vec<String>,
+    // Rows of the other file in its order, with whether they matched a row
+    right_rows: Vec<(StringRecord, bool)>,
+    right_indices: HashMap<Vec<String>, Vec<usize>>,
+}
+
+impl Join {
+    /// Reads the other file, with the key columns of --on
+    pub fn new(headers: &StringRecord, path: &str, other: Input, on_str: &str,
+               join_type: JoinType) -> Result<Self, Box<dyn Error>> {
+        let (left_keys, right_keys) = parse_on(on_str, headers, &other.headers, path)?;
+        let right_columns: Vec<usize> = (0..other.headers.len()).filter(|column| !right_keys.contains(column)).collect();
+        let right_headers = other_headers(headers, path, right_columns.iter().map(|&column| &other.headers[column]));
+
+        let (mut right_rows, mut right_indices) = (Vec::new(), HashMap::<Vec<String>, Vec<usize>>::new());
+        for record in other.records {
+            let record = record?;
+            if let Some(key) = key(&record, &right_keys) {
+                right_indices.entry(key).or_default().push(right_rows.len());
+            }
+            right_rows.push((record, false));
+        }
+        Ok(Self { join_type, left_columns: headers.len(), left_keys, right_keys, right_columns, right_headers, right_rows, right_indices })
+    }
+
+    /// The headers of the rows followed by the ones of the other file
+    pub fn headers(&self, headers: &StringRecord) -> StringRecord {
+        headers.iter().chain(self.right_headers.iter().map(String::as_str)).collect()
+    }
+
+    /// The row joined with each row of the other file with its key. Without
+    /// any, none or the row with empty fields for a left or full join
+    fn rows(&mut self, record: &StringRecord) -> Vec<StringRecord> {
+        let Some(matches) = key(record, &self.left_keys).and_then(|key| self.right_indices.get(&key)) else {
+            return match self.join_type {
+                JoinType::Left | JoinType::Full => vec![combine(record, &StringRecord::new(), &self.right_columns)],
+                JoinType::Inner | JoinType::Right => Vec::new(),
+            };
+        };
+        let mut rows = Vec::with_capacity(matches.len());
+        for &i in matches {
+            self.right_rows[i].1 = true;
+            rows.push(combine(record, &self.right_rows[i].0, &self.right_columns));
+        }
+        rows
+    }
+
+    /// The rows of the other file that didn't match any row, for a right or
+    /// full join. Their key goes in the key columns of the rows
+    fn unmatched_rows(&self) -> Vec<StringRecord> {
+        if !matches!(self.join_type, JoinType::Right | JoinType::Full) {
+            return Vec::new();
+        }
+        self.right_rows.iter().filter(|(_, matched)| !matched)
+            .map(|(right, _)| {
+                let mut left = vec![""; self.left_columns];
+                for (&left_key, &right_key) in self.left_keys.iter().zip(&self.right_keys) {
+                    left[left_key] = right.get(right_key).unwrap_or_default();
+                }
+                combine(&StringRecord::from(left), right, &self.right_columns)
+            })
+            .collect()
+    }
+
+    /// Joins a stream of rows, followed by the unmatched rows of the other
+    /// file, which are numbered after the last row
+    pub fn join<I: Iterator<Item = IndexedRecord>>(self, rows: I) -> Joined<I> {
+        Joined { join: self, rows, pending: VecDeque::new(), rows_seen: 0, finished: false }
+    }
+}
+
+pub struct Joined<I> {
+    join: Join,
+    rows: I,
+    pending: VecDeque<IndexedRecord>,
+    rows_seen: usize,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = IndexedRecord>> Iterator for Joined<I> {
+    type Item = IndexedRecord;
+
+    fn next(&mut self) -> Option<IndexedRecord> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Some(row);
+            }
+            if self.finished {
+                return None;
+            }
+            match self.rows.next() {
+                Some(Ok((row_index, record))) => {
+                    self.rows_seen = row_index + 1;
+                    self.pending.extend(self.join.rows(&record).into_iter().map(|record| Ok((row_index, record))));
+                },
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.finished = true;
+                    let rows_seen = self.rows_seen;
+                    self.pending.extend(self.join.unmatched_rows().into_iter().enumerate()
+                        .map(|(i, record)| Ok((rows_seen + i, record))));
+                },
+            }
+        }
+    }
+}
+
+/// Keys of another file, to keep the rows with one of them for --semi-join,
+/// or without any for --anti-join. Only the keys are kept in memory
+pub struct KeyFilter {
+    keys: Vec<usize>,
+    other_keys: HashSet<Vec<String>>,
+    anti: bool,
+}
+
+impl KeyFilter {
+    pub fn new(headers: &StringRecord, path: &str, other: Input, on_str: &str, anti: bool) -> Result<Self, Box<dyn Error>> {
+        let (keys, right_keys) = parse_on(on_str, headers, &other.headers, path)?;
+        let mut other_keys = HashSet::new();
+        for record in other.records {
+            other_keys.extend(key(&record?, &right_keys));
+        }
+        Ok(Self { keys, other_keys, anti })
+    }
+
+    pub fn accepts(&self, record: &StringRecord) -> bool {
+        let found = key(record, &self.keys).is_some_and(|key| self.other_keys.contains(&key));
+        found != self.anti
+    }
+}
+
+/// Rows of another file pasted after the rows, in the same order, for
+/// --paste. Only one row of each file is in memory at a time
+pub struct Paste {
+    path: String,
+    other_headers: Vec<String>,
+    other_records: RecordIter,
+    columns: usize,
+    other_columns: usize,
+    pad: bool,
+}
+
+impl Paste {
+    pub fn new(headers: &StringRecord, path: &str, other: Input, pad: bool) -> Self {
+        Self {
+            path: String::from(path),
+            other_headers: other_headers(headers, path, other.headers.iter()),
+            other_records: other.records,
+            columns: headers.len(),
+            other_columns: other.headers.len(),
+            pad,
+        }
+    }
+
+    /// The headers of the rows followed by the ones of the other file
+    pub fn headers(&self, headers: &StringRecord) -> StringRecord {
+        headers.iter().chain(self.other_headers.iter().map(String::as_str)).collect()
+    }
+
+    /// Pastes a stream of rows. When one of the files has more rows the
+    /// other one gets empty fields with pad, or it's an error
+    pub fn paste<I: Iterator<Item = Result<StringRecord, Box<dyn Error>>>>(self, rows: I) -> Pasted<I> {
+        Pasted { paste: self, rows, rows_seen: 0 }
+    }
+}
+
+pub struct Pasted<I> {
+    paste: Paste,
+    rows: I,
+    rows_seen: usize,
+}
+
+impl<I: Iterator<Item = Result<StringRecord, Box<dyn Error>>>> Iterator for Pasted<I> {
+    type Item = Result<StringRecord, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let paste = &mut self.paste;
+        let (row, other_row) = match (self.rows.next(), paste.other_records.next()) {
+            (None, None) => return None,
+            (Some(Err(err)), _) | (_, Some(Err(err))) => return Some(Err(err)),
+            (Some(Ok(row)), Some(Ok(other_row))) => (row, other_row),
+            (row, other_row) if paste.pad => {
+                (row.and_then(Result::ok).unwrap_or_default(), other_row.and_then(Result::ok).unwrap_or_default())
+            },
+            (row, _) => {
+                let shorter = match row {
+                    Some(_) => paste.path.as_str(),
+                    None => "the input",
+                };
+                return Some(Err(format!("--paste: {} ends after {} rows, use --pad to fill in the missing rows",
+                                        shorter, self.rows_seen).into()));
+            },
+        };
+        self.rows_seen += 1;
+        // Short rows are filled in so the columns of the other file line up
+        let mut pasted = StringRecord::new();
+        for (record, columns) in [(&row, paste.columns), (&other_row, paste.other_columns)] {
+            pasted.extend(record.iter().chain(std::iter::repeat_n("", columns.saturating_sub(record.len()))));
+        }
+        Some(Ok(pasted))
+    }
+}
+
+/// Headers of another file added to the headers, where the names already
+/// found in them are prefixed by the name of the file
+fn other_headers<'a>(headers: &StringRecord, path: &str, names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let file_name = Path::new(path).file_name().map_or(path.into(), |name| name.to_string_lossy());
+    let prefix = file_name.split('.').next().unwrap_or_default();
+    names
+        .map(|name| match headers.iter().any(|header| header == name) {
+            true => format!("{}_{}", prefix, name),
+            false => String::from(name),
+        })
+        .collect()
+}
+
+/// A row followed by the columns of a row of the other file
+fn combine(left: &StringRecord, right: &StringRecord, right_columns: &[usize]) -> StringRecord {
+    left.iter()
+        .chain(right_columns.iter().map(|&column| right.get(column).unwrap_or_default()))
+        .collect()
+}
+
+/// Values of the key columns, None when they are all empty
+fn key(record: &StringRecord, columns: &[usize]) -> Option<Vec<String>> {
+    let key: Vec<String> = columns.iter().map(|&column| String::from(record.get(column).unwrap_or_default())).collect();
+    match key.iter().all(String::is_empty) {
+        true => None,
+        false => Some(key),
+    }
+}
+
+#[test]
+fn test_join() {
+    use crate::input::test_input;
+
+    let headers = StringRecord::from(vec!["order", "customer_id", "name"]);
+    let customers = || test_input(&[
+        &["id", "name", "city"], &["1", "Ada", "London"], &["2", "Alan", "Wilmslow"], &["1", "Ada L.", "Paris"], &["", "Nobody", ""],
+    ]);
+    let orders = || [["10", "1", "x"], ["11", "3", "y"], ["12", "", "z"]].into_iter()
+        .enumerate()
+        .map(|(i, row)| Ok((i, StringRecord::from(row.to_vec()))));
+    let join = |join_type: JoinType| {
+        let join = Join::new(&headers, "data/customers.csv.gz", customers(), "customer_id=id", join_type).unwrap();
+        join.join(orders()).map(Result::unwrap).collect::<Vec<(usize, StringRecord)>>()
+    };
+    let row = |i: usize, row: [&str; 5]| (i, StringRecord::from(row.to_vec()));
+
+    let inner = vec![row(0, ["10", "1", "x", "Ada", "London"]), row(0, ["10", "1", "x", "Ada L.", "Paris"])];
+    assert_eq!(join(JoinType::Inner), inner);
+    let left = [inner.clone(), vec![row(1, ["11", "3", "y", "", ""]), row(2, ["12", "", "z", "", ""])]].concat();
+    assert_eq!(join(JoinType::Left), left);
+    let unmatched = vec![row(3, ["", "2", "", "Alan", "Wilmslow"]), row(4, ["", "", "", "Nobody", ""])];
+    assert_eq!(join(JoinType::Right), [inner, unmatched.clone()].concat());
+    assert_eq!(join(JoinType::Full), [left, unmatched].concat());
+
+    let join = Join::new(&headers, "data/customers.csv.gz", customers(), "customer_id=id", JoinType::Inner).unwrap();
+    assert_eq!(join.headers(&headers), vec!["order", "customer_id", "name", "customers_name", "city"]);
+    assert!(Join::new(&headers, "c.csv", test_input(&[&["id"]]), "id", JoinType::Inner).is_err());
+    assert!(Join::new(&headers, "c.csv", test_input(&[&["id"]]), "customer_id=key", JoinType::Inner).is_err());
+}
+
+#[test]
+fn test_key_filter() {
+    use crate::input::test_input;
+
+    let headers = StringRecord::from(vec!["id", "name"]);
+    let keys = || test_input(&[&["user", "reason"], &["1", "spam"], &["3", "bots"], &["", "none"]]);
+    let records = [["1", "a"], ["2", "b"], ["", "c"]].map(|row| StringRecord::from(row.to_vec()));
+
+    let semi = KeyFilter::new(&headers, "banned.csv", keys(), "id=user", false).unwrap();
+    assert_eq!(records.iter().map(|record| semi.accepts(record)).collect::<Vec<bool>>(), vec![true, false, false]);
+    let anti = KeyFilter::new(&headers, "banned.csv", keys(), "id=user", true).unwrap();
+    assert_eq!(records.iter().map(|record| anti.accepts(record)).collect::<Vec<bool>>(), vec![false, true, true]);
+    assert!(KeyFilter::new(&headers, "banned.csv", keys(), "id", false).is_err());
+}
+
+#[test]
+fn test_paste() {
+    use crate::input::test_input;
+
+    let headers = StringRecord::from(vec!["id", "name"]);
+    let rows = || [["1", "Ada"], ["2", "Alan"]].into_iter().map(|row| Ok(StringRecord::from(row.to_vec())));
+    let other = || test_input(&[&["name", "city"], &["Ada", "London"], &["Alan", "Wilmslow"], &["Cy", "Paris"]]);
+
+    let paste = Paste::new(&headers, "data/cities.csv", other(), false);
+    assert_eq!(paste.headers(&headers), vec!["id", "name", "cities_name", "city"]);
+    let pasted: Vec<_> = paste.paste(rows()).collect();
+    assert_eq!(pasted[1].as_ref().unwrap(), &StringRecord::from(vec!["2", "Alan", "Alan", "Wilmslow"]));
+    assert!(pasted[2].is_err());
+
+    let pasted: Vec<StringRecord> = Paste::new(&headers, "cities.csv", other(), true).paste(rows()).map(Result::unwrap).collect();
+    assert_eq!(pasted.len(), 3);
+    assert_eq!(pasted[2], StringRecord::from(vec!["", "", "Cy", "Paris"]));
+}
@@ -1,18 +1,156 @@
-use clap::Parser;
-use std::collections::HashMap;
-use std::{error::Error, process};
+mod agg;
+mod columns;
+mod diff;
+mod filter;
+mod input;
+mod join;
+mod output;
+mod profile;
+mod reshape;
+mod sample;
+mod setops;
+mod sort;
+mod split;
+mod unique;
+mod value;
+
+use clap::{Parser, Subcommand};
+use rand::RngExt;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 use csv::StringRecord;
+use std::{error::Error, process};
+use filter::{FilterExpr, ROW_NUMBER_COLUMN};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Input file to process
-    file: String,
+    /// Input files or HTTP(S) URLs to process, read as a single table. Stdin when it's "-" or missing
+    files: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Format of the input, by default given by the extension: .jsonl and
+    /// .ndjson for JSON Lines, .parquet for parquet, .xlsx for Excel and CSV otherwise
+    #[arg(long, value_enum, global = true)]
+    input_format: Option<input::InputFormat>,
+
+    /// Sheet of the xlsx inputs, the first one by default
+    #[arg(long, global = true)]
+    sheet: Option<String>,
+
+    /// Read a fixed-width file with these columns, 0-based character ranges like "0-9,10-24".
+    /// Also the path of a file with a range per line, optionally after the column name: "id 0-9"
+    #[arg(long, value_parser = input::parse_fwf, conflicts_with = "input_format", global = true)]
+    fwf: Option<input::FwfSpec>,
+
+    /// Compression of the input, by default given by the extension: .gz, .zst, .bz2 or .xz
+    #[arg(long, value_enum, global = true)]
+    compression: Option<input::Compression>,
+
+    /// Field delimiter of the input, a single character like ';', '|' or '\t'.
+    /// Tabs for .tsv and .tab files and commas otherwise by default
+    #[arg(short, long, value_parser = input::parse_byte, global = true)]
+    delimiter: Option<u8>,
+
+    /// Ignore the lines starting with this character, e.g. '#'
+    #[arg(long, value_parser = input::parse_byte, global = true)]
+    comment: Option<u8>,
+
+    /// Encoding of the input, e.g. latin1, windows-1252 or utf-16le. UTF-8 by default
+    #[arg(long, value_parser = input::parse_encoding, global = true)]
+    encoding: Option<&'static encoding_rs::Encoding>,
+
+    /// Guess the delimiter and quote from the start of the input. The default
+    /// for stdin and files that are not .csv, .tsv or .tab
+    #[arg(long, conflicts_with = "delimiter", global = true)]
+    sniff: bool,
+
+    /// Lines to skip before the header, for files starting with metadata
+    #[arg(long, default_value_t = 0, global = true)]
+    skip_lines: usize,
+
+    /// The first row is data instead of the header. Columns are named c1, c2...
+    #[arg(long, global = true)]
+    no_header: bool,
+
+    /// Keep printing the rows appended to the input, like tail -f, until interrupted.
+    /// The rows are not limited by -n, and the delimiter is not guessed
+    #[arg(long, conflicts_with_all = ["agg", "info", "sniff"])]
+    follow: bool,
+
+    /// Accept rows with a different number of fields than the header: short
+    /// rows are padded with empty fields and long ones truncated
+    #[arg(long, global = true)]
+    flexible: bool,
+
+    /// Decompress gzip input, same as --compression gzip
+    #[arg(long, conflicts_with = "compression", global = true)]
+    gzip: bool,
+
+    /// Rename columns, e.g. "cust_id=customer_id,amt=amount". The other options use the new names
+    #[arg(long)]
+    rename: Option<String>,
+
+    /// Append a column computed from the others, e.g. "total=price*qty". Accepts
+    /// + - * / % and parentheses, and can be repeated
+    #[arg(long)]
+    derive: Vec<String>,
+
+    /// Normalize the values of columns, e.g. "name=trim|lower,city=upper". Functions:
+    /// lower, upper, trim, title and squish, which also collapses inner spaces
+    #[arg(long)]
+    transform: Option<String>,
+
+    /// Reformat the dates of a column, e.g. "created_at:%d/%m/%Y->%Y-%m-%d". An empty
+    /// input format reads only ISO dates. Values that don't parse are kept. Can be repeated
+    #[arg(long)]
+    date: Vec<String>,
+
+    /// sed-style regex substitution, e.g. "phone:s/[^0-9]//g", or "s/a/b/" for every
+    /// column. Flags: g and i. Can be repeated
+    #[arg(long)]
+    replace: Vec<String>,
+
+    /// Split a column on a delimiter into new columns, e.g. "full_name: :first,last". The
+    /// last new column gets the rest of the value. Can be repeated
+    #[arg(long)]
+    split_col: Vec<String>,
+
+    /// Join columns and quoted texts into a new column, e.g. "address=street+', '+city".
+    /// Can be repeated
+    #[arg(long)]
+    merge_cols: Vec<String>,
+
+    /// Replace the empty values of a column, e.g. "country=unknown". Can be repeated
+    #[arg(long)]
+    fill_na: Vec<String>,
+
+    /// Replace the empty values of every column. Unlike --null-as, the filters and the
+    /// typed formats see the value
+    #[arg(long)]
+    fill_na_all: Option<String>,
+
+    /// Check and normalize the values of columns to a type, e.g. "amount:float,id:int,flag:bool".
+    /// Empty values are kept
+    #[arg(long)]
+    cast: Option<String>,
+
+    /// What to do with a value that --cast can't convert
+    #[arg(long, value_enum, default_value_t = columns::CastError::Error, requires = "cast")]
+    cast_error: columns::CastError,
 
     /// Columns to display
     #[arg(short, long)]
     cols: Option<String>,
 
+    /// Columns to leave out, displaying all the others
+    #[arg(long, conflicts_with = "cols")]
+    exclude_cols: Option<String>,
+
     /// Filtering columns by some value
     #[arg(short, long)]
     filter: Option<String>,
@@ -28,268 +166,1098 @@ struct Args {
     /// Display CSV info
     #[arg(short, long)]
     info: bool,
+
+    /// With --info, estimate the rows from the size of the file and the rows in its first
+    /// bytes, 8MB by default, like 64MB, instead of reading it all. The columns are profiled
+    /// from those rows
+    #[arg(long, requires = "info", num_args = 0..=1, default_missing_value = "8MB", value_parser = split::parse_size)]
+    estimate: Option<u64>,
+
+    /// chrono format used to parse dates in filters, e.g. "%d/%m/%Y"
+    #[arg(long)]
+    date_format: Option<String>,
+
+    /// Display the rows rejected by the filter instead
+    #[arg(short = 'v', long, requires = "filter")]
+    invert_match: bool,
+
+    /// Aggregate the selected columns (all of them without --cols): sum, avg, min, max, count, stdp, stds, var, count_distinct, approx_count_distinct or mode
+    #[arg(long)]
+    agg: Option<String>,
+
+    /// Join each row with the rows of this file with the same key, given by --on. The
+    /// file is read into memory
+    #[arg(long, requires = "on")]
+    join: Option<String>,
+
+    /// Rows kept by --join
+    #[arg(long, value_enum, default_value_t = join::JoinType::Inner, requires = "join")]
+    join_type: join::JoinType,
+
+    /// Keep only the rows with a key found in this file, given by --on
+    #[arg(long, requires = "on", conflicts_with_all = ["join", "anti_join"])]
+    semi_join: Option<String>,
+
+    /// Keep only the rows with a key not found in this file, given by --on
+    #[arg(long, requires = "on", conflicts_with = "join")]
+    anti_join: Option<String>,
+
+    /// Paste the columns of this file after the ones of the rows, row by row. Both files
+    /// must have the same number of rows, unless --pad
+    #[arg(long)]
+    paste: Option<String>,
+
+    /// Fill in with empty fields the rows missing in the shorter file of --paste
+    #[arg(long, requires = "paste")]
+    pad: bool,
+
+    /// Key columns of --join, --semi-join and --anti-join, e.g. "id", "id,date", or
+    /// "customer_id=id" when the other file names them differently
+    #[arg(long)]
+    on: Option<String>,
+
+    /// Reshape wide columns into rows of (variable, value), e.g. "id_cols=date, value_cols=us,eu,apac".
+    /// Without value_cols every other column is melted
+    #[arg(long, conflicts_with = "pivot")]
+    melt: Option<String>,
+
+    /// A row for each value of a column with several ones, given the column and their
+    /// delimiter, e.g. "tags:|". The other columns are repeated
+    #[arg(long)]
+    explode: Option<String>,
+
+    /// Reshape long rows into a wide table, e.g. "index=date, columns=region, values=sales, agg=sum".
+    /// The aggregate is one of --agg, sum by default
+    #[arg(long, conflicts_with_all = ["agg", "running", "line_numbers", "cols", "exclude_cols", "sort", "tail", "sample", "follow"])]
+    pivot: Option<String>,
+
+    /// Print the count, mean, std, min, quartiles and max of each column (of --cols when given),
+    /// or only the count and distinct values for text columns
+    #[arg(long, conflicts_with_all = ["agg", "pivot", "running", "line_numbers", "sort", "tail", "sample", "follow", "info"])]
+    stats: bool,
+
+    /// Print each value of this column with its count and percentage of the rows, the most
+    /// common first
+    #[arg(long, conflicts_with_all = ["agg", "pivot", "running", "line_numbers", "sort", "tail", "sample", "follow", "info", "stats"])]
+    freq: Option<String>,
+
+    /// Only print this many of the most common values of --freq
+    #[arg(long, requires = "freq")]
+    top: Option<usize>,
+
+    /// Print the Pearson correlations between these columns, e.g. "price,qty,discount", or
+    /// between all the numeric columns when none are given
+    #[arg(long, num_args = 0..=1, default_missing_value = "",
+          conflicts_with_all = ["agg", "pivot", "running", "line_numbers", "sort", "tail", "sample", "follow", "info", "stats", "freq", "hist"])]
+    corr: Option<String>,
+
+    /// Print the rows with an outlier value in this column, with their row numbers
+    #[arg(long, conflicts_with_all = ["agg", "pivot", "running", "sort", "tail", "sample", "follow", "info", "stats", "freq",
+                                      "corr", "find_duplicates"])]
+    outliers: Option<String>,
+
+    /// How --outliers finds the outliers
+    #[arg(long, value_enum, default_value_t = profile::OutlierMethod::Zscore, requires = "outliers")]
+    method: profile::OutlierMethod,
+
+    /// Standard deviations of --method zscore, 3 by default, or interquartile ranges of
+    /// --method iqr, 1.5 by default
+    #[arg(long, requires = "outliers")]
+    threshold: Option<f64>,
+
+    /// Print a histogram of the numbers of this column, with bars drawn in the terminal
+    #[arg(long, conflicts_with_all = ["agg", "pivot", "running", "line_numbers", "sort", "tail", "sample", "follow", "info", "stats", "freq", "outliers",
+                                     "output", "format", "to_sqlite"])]
+    hist: Option<String>,
+
+    /// Bins of --hist, of the same width between the min and max of the column
+    #[arg(long, default_value_t = 20, requires = "hist", value_parser = clap::value_parser!(u32).range(1..))]
+    bins: u32,
+
+    /// Columns to group the aggregates by, e.g. --group-by region --agg sum:amount,count
+    #[arg(long, requires = "agg")]
+    group_by: Option<String>,
+
+    /// Filter applied to the aggregated rows, e.g. --having "sum_amount>1000"
+    #[arg(long, requires = "agg")]
+    having: Option<String>,
+
+    /// Cumulative aggregates appended to each row, e.g. --running sum:amount
+    #[arg(long, conflicts_with = "agg")]
+    running: Option<String>,
+
+    /// Prefix each row with its 1-based row number in the file, counted before --offset and --filter
+    #[arg(long, conflicts_with = "agg")]
+    line_numbers: bool,
+
+    /// Sort the filtered rows by these columns before --offset and -n, e.g. "size:desc,name".
+    /// Numbers and dates are compared as such
+    #[arg(long, conflicts_with_all = ["agg", "follow"])]
+    sort: Option<String>,
+
+    /// Display the last N rows that pass the filter instead of the first ones.
+    /// Plain CSV files are read from their end when nothing else needs the whole file
+    #[arg(long, conflicts_with_all = ["agg", "sort", "follow", "n", "offset"])]
+    tail: Option<usize>,
+
+    /// Drop the rows equal to a previous one
+    #[arg(long, conflicts_with = "unique_by")]
+    unique: bool,
+
+    /// Drop the rows with the same values in these columns as a previous one, e.g. "id" or "email,day"
+    #[arg(long)]
+    unique_by: Option<String>,
+
+    /// Print the rows repeated, or the keys of --by, with their count and row numbers
+    #[arg(long, conflicts_with_all = ["agg", "pivot", "running", "line_numbers", "sort", "tail", "sample", "follow", "info",
+                                      "stats", "freq", "hist", "corr", "unique", "unique_by"])]
+    find_duplicates: bool,
+
+    /// Key columns of --find-duplicates, e.g. "id" or "email,day"
+    #[arg(long, requires = "find_duplicates")]
+    by: Option<String>,
+
+    /// Row kept of each duplicate with --unique or --unique-by. The last ones
+    /// are only known once the whole input was read
+    #[arg(long, value_enum, default_value_t = unique::Keep::First, conflicts_with = "follow")]
+    keep: unique::Keep,
+
+    /// Display N rows chosen at random, in the order of the input, instead of the first ones
+    #[arg(long, conflicts_with_all = ["agg", "sort", "tail", "follow", "n"])]
+    sample: Option<usize>,
+
+    /// Keep each row with this probability, e.g. 0.01 for about 1% of the rows
+    #[arg(long, value_parser = sample::parse_fraction, conflicts_with = "sample")]
+    sample_frac: Option<f64>,
+
+    /// Display the rows in a random order. Only the displayed rows are
+    /// shuffled, after --offset, -n and the other options
+    #[arg(long, conflicts_with = "follow")]
+    shuffle: bool,
+
+    /// Seed of --sample, --sample-frac and --shuffle, to get the same rows every time
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = output::Format::Csv)]
+    format: output::Format,
+
+    /// Write the output to a file instead of stdout. The file is only
+    /// replaced once everything was written
+    #[arg(long)]
+    output: Option<String>,
+
+    /// When to quote the fields of the csv format
+    #[arg(long, value_enum, default_value_t = output::QuoteStyle::Necessary)]
+    quote_style: output::QuoteStyle,
+
+    /// Column types of the typed formats like parquet, e.g. "id:int,price:float,zip:text".
+    /// The other columns are inferred from their values
+    #[arg(long)]
+    schema: Option<String>,
+
+    /// Width of the columns of the plain format, longer values are cut with "…"
+    #[arg(long, default_value_t = 20)]
+    max_col_width: usize,
+
+    /// Text shown for the empty fields, e.g. NULL or -
+    #[arg(long)]
+    null_as: Option<String>,
+
+    /// Create a table with the rows in this SQLite database, instead of printing them
+    #[arg(long, conflicts_with = "output")]
+    to_sqlite: Option<String>,
+
+    /// Table created by --to-sqlite, the input file name by default
+    #[arg(long, requires = "to_sqlite")]
+    table: Option<String>,
+}
+
+/// Commands working on a couple of files, instead of displaying the rows
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare two files by their whole rows, reporting the rows added and removed
+    Diff(DiffArgs),
+    /// Compare the columns of two files, their names, order and inferred types
+    SchemaDiff(SchemaDiffArgs),
+    /// Write the rows of a file to several ones, each with the header
+    Split(SplitArgs),
+    /// Print the rows of both files, each one once
+    Union(SetArgs),
+    /// Print the rows of the first file that are also in the second one
+    Intersect(SetArgs),
+    /// Print the rows of the first file that are not in the second one
+    Except(SetArgs),
 }
 
-#[derive(PartialEq)]
-enum RowFilterOperator {
-    Equal,
-    Lesser,
-    Greater,
-    EqualString
+#[derive(clap::Args, Debug)]
+#[group(skip)]
+#[command(group(clap::ArgGroup::new("mode").required(true).args(["chunk_rows", "chunk_size", "by"])))]
+struct SplitArgs {
+    file: String,
+
+    /// Start a new file every this many rows
+    #[arg(long, value_parser = split::parse_rows)]
+    chunk_rows: Option<usize>,
+
+    /// Start a new file before going over about this size, e.g. 64K, 10MB or 1G
+    #[arg(long, value_parser = split::parse_size)]
+    chunk_size: Option<u64>,
+
+    /// Write a file for each value of this column
+    #[arg(long)]
+    by: Option<String>,
+
+    /// Names of the files, with {name} for the input name and {n} for the number of the
+    /// file or {value} for the value of --by. "{name}-{n}.csv" or "{name}-{value}.csv" by default
+    #[arg(long)]
+    template: Option<String>,
 }
 
-struct RowFilter {
-    left_column: Option<usize>,
-    right_column: Option<usize>,  // not used, but left for future flexibility
-    left_value: Option<String>,   // not used, but left for future flexibility
-    right_value: Option<String>,
-    operator: RowFilterOperator,
+#[derive(clap::Args, Debug)]
+struct SetArgs {
+    first: String,
+
+    second: String,
+
+    /// Compare the rows by these key columns instead of the whole rows, e.g. "id" or "id,date"
+    #[arg(long)]
+    key: Option<String>,
 }
 
-impl RowFilter {
-    fn new(filter_str: &str, col_idx_dict: HashMap<String, usize>) -> Self {
-        let operator : RowFilterOperator;
-        
-        let left_and_right: Vec<&str>;
-        let left_column: usize;
-        let right_value: &str;
-        if filter_str.contains("<") || filter_str.contains(">") || filter_str.contains("==") {
-            if filter_str.contains("<") {
-                left_and_right = filter_str.split('<').collect();
-                operator = RowFilterOperator::Lesser;
-            } else if filter_str.contains(">") {
-                left_and_right = filter_str.split('>').collect();
-                operator = RowFilterOperator::Greater;
-            } else {
-                left_and_right = filter_str.split("==").collect();
-                operator = RowFilterOperator::Equal;
-            }
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// The file compared against
+    old: String,
 
-            // In this case, left should be the column
-            // And right should be the value
-            left_column = *col_idx_dict.get(left_and_right[0]).unwrap();
+    /// The new version of the file
+    new: String,
 
-            // We get the index column
-            right_value = left_and_right[1];
+    /// Print the added and removed rows as a CSV with a leading diff column of + and -.
+    /// The counts then go to stderr
+    #[arg(long, conflicts_with = "key")]
+    rows: bool,
 
-            if left_and_right.len() != 2 {
-                panic!("Wrong formatted filter: {}", filter_str);
-            }
+    /// Match the rows by these key columns, e.g. "id" or "id,date", and print the columns
+    /// changed in each, like "id=42: price 10.0 -> 12.5"
+    #[arg(long)]
+    key: Option<String>,
+}
 
-            return Self {
-                left_column: Some(left_column),
-                right_column: None,
-                left_value: None,
-                right_value: Some(String::from(right_value)),
-                operator: operator,
-            };
-        } else if filter_str.contains("=") {
-            // In this case, left and right are treated as strings
-            operator = RowFilterOperator::EqualString;
-            left_and_right = filter_str.split('=').collect();
-
-            // In this case, left should be the column
-            // And right should be the value
-            left_column = *col_idx_dict.get(left_and_right[0]).unwrap();
-            // We get the index column
-            right_value = left_and_right[1];
-
-            if left_and_right.len() != 2 {
-                panic!("Wrong formatted filter: {}", filter_str);
-            }
+#[derive(clap::Args, Debug)]
+struct SchemaDiffArgs {
+    /// The file compared against
+    old: String,
 
-            return Self {
-                left_column: Some(left_column),
-                right_column: None,
-                left_value: None,
-                right_value: Some(String::from(right_value)),
-                operator: operator,
-            };
-        } else {
-            panic!("No operator for filter string {}", filter_str);
-        }
-    }
+    /// The new version of the file
+    new: String,
 
-    fn accepts(&self, row: StringRecord) -> bool {
-        match self.operator {
-            RowFilterOperator::EqualString => {
-                let left_value = row.get(self.left_column.unwrap()).unwrap();
-                let right_value = self.right_value.as_ref().unwrap().as_str();
-                return left_value == right_value;
-            },
-            RowFilterOperator::Equal => {
-                let left_value = row.get(self.left_column.unwrap()).unwrap().parse::<f32>().unwrap();
-                let right_value = self.right_value.as_ref().unwrap().as_str().parse::<f32>().unwrap();
-                return left_value == right_value;
-            },
-            RowFilterOperator::Lesser => {
-                let left_value = row.get(self.left_column.unwrap()).unwrap().parse::<f32>().unwrap();
-                let right_value = self.right_value.as_ref().unwrap().as_str().parse::<f32>().unwrap();
-                return left_value < right_value;
-            },
-            RowFilterOperator::Greater => {
-                let left_value = row.get(self.left_column.unwrap()).unwrap().parse::<f32>().unwrap();
-                let right_value = self.right_value.as_ref().unwrap().as_str().parse::<f32>().unwrap();
-                return left_value > right_value;
-            }
-        }
+    /// Rows of each file read to infer the types of the columns
+    #[arg(long, default_value_t = 1000)]
+    infer_rows: usize,
+}
+
+/// How the inputs are read, shared by the commands
+fn reader_options(args: &Args) -> input::ReaderOptions {
+    input::ReaderOptions {
+        format: args.input_format,
+        compression: match args.gzip {
+            true => Some(input::Compression::Gzip),
+            false => args.compression,
+        },
+        encoding: args.encoding,
+        skip_lines: args.skip_lines,
+        delimiter: args.delimiter,
+        sniff: args.sniff,
+        has_headers: !args.no_header,
+        comment: args.comment,
+        follow: args.follow,
+        flexible: args.flexible,
+        sheet: args.sheet.clone(),
+        fwf: args.fwf.clone(),
     }
 }
 
-fn read_csv(csv: &str, cols: Option<String>,
-            offset: u32,
-            max_rows: u32, info: bool,
-            filters: Option<String>
-) -> Result<(), Box<dyn Error>> {
-    // Build the CSV reader and iterate over each record.
-    let mut rdr = csv::Reader::from_path(csv)?;
+fn run_set_operation(operation: setops::SetOperation, set_args: &SetArgs, args: &Args) -> Result<(), Box<dyn Error>> {
+    setops::run(operation, &set_args.first, &set_args.second, set_args.key.as_deref(), &reader_options(args))
+}
 
-    if info {
-        let mut n_cols : u32 = 0;
-        println!("CSV columns:");
-        for header in rdr.headers().unwrap().iter() {
-            println!("{}", header);
-            n_cols += 1;
+fn read_csv(args: &Args) -> Result<(), Box<dyn Error>> {
+    let reader_options = reader_options(args);
+    // Several files are read one after the other, as a single CSV
+    let files = input::expand_globs(&args.files)?;
+    let first_path = files.first().map(String::as_str);
+    let first_input = input::open_input(first_path, &reader_options)?;
+    let sniffed = first_input.sniffed;
+    let dialect = sniffed.unwrap_or(input::Dialect {
+        delimiter: args.delimiter.unwrap_or_else(|| input::default_delimiter(first_path)),
+        quote: b'"',
+    });
+    let first_headers = first_input.headers.clone();
+    let rest = files.iter().skip(1).cloned().collect();
+    let records = input::Records::new(first_input, first_path.unwrap_or(input::STDIN_PATH), rest, &reader_options);
+
+    // Without a header the first row is data, and the columns are named by
+    // their position: c1, c2...
+    let header_record = match args.no_header {
+        true => StringRecord::from((1..=first_headers.len()).map(|i| format!("c{}", i)).collect::<Vec<String>>()),
+        false => first_headers,
+    };
+    // --paste adds the columns of another file, so they can be renamed
+    let paste = match args.paste {
+        Some(ref paste_path) => {
+            let other = input::open_input(Some(paste_path), &reader_options.for_other_file())?;
+            Some(join::Paste::new(&header_record, paste_path, other, args.pad))
+        },
+        None => None,
+    };
+    let header_record = match (&paste, args.no_header) {
+        (Some(paste), false) => paste.headers(&header_record),
+        (Some(paste), true) => {
+            let columns = paste.headers(&header_record).len();
+            StringRecord::from((1..=columns).map(|i| format!("c{}", i)).collect::<Vec<String>>())
+        },
+        (None, _) => header_record,
+    };
+    // Renamed columns are referred to by their new names from here on
+    let mut header_record = match args.rename {
+        Some(ref rename_str) => columns::rename(&header_record, rename_str)?,
+        None => header_record,
+    };
+    // Fields in the records as they're read
+    let input_columns = header_record.len();
+    // The values are normalized and the derived columns added to the
+    // records before the filters
+    let mut pipeline = columns::Pipeline::default();
+    if let Some(ref transform_str) = args.transform {
+        pipeline.transform(&header_record, transform_str)?;
+    }
+    for date_str in &args.date {
+        pipeline.date(&header_record, date_str)?;
+    }
+    for replace_str in &args.replace {
+        pipeline.replace(&header_record, replace_str)?;
+    }
+    for split_str in &args.split_col {
+        pipeline.split(&mut header_record, split_str)?;
+    }
+    for merge_str in &args.merge_cols {
+        pipeline.merge(&mut header_record, merge_str)?;
+    }
+    for derive_str in &args.derive {
+        pipeline.derive(&mut header_record, derive_str)?;
+    }
+    // Filled last, so the defaults also apply to the new columns
+    for fill_str in &args.fill_na {
+        pipeline.fill_na(&header_record, fill_str)?;
+    }
+    if let Some(ref default) = args.fill_na_all {
+        pipeline.fill_na_all(default);
+    }
+    if let Some(ref cast_str) = args.cast {
+        pipeline.cast(&header_record, cast_str, args.cast_error)?;
+    }
+    // --semi-join and --anti-join keep the rows by their key in another file
+    let key_filter = match (&args.semi_join, &args.anti_join) {
+        (Some(path), _) | (_, Some(path)) => {
+            let other = input::open_input(Some(path), &reader_options.for_other_file())?;
+            let on_str = args.on.as_deref().unwrap_or_default();
+            Some(join::KeyFilter::new(&header_record, path, other, on_str, args.anti_join.is_some())?)
+        },
+        (None, None) => None,
+    };
+    // --join adds the columns of the matching rows of another file
+    let join = match args.join {
+        Some(ref join_path) => {
+            let other = input::open_input(Some(join_path), &reader_options.for_other_file())?;
+            Some(join::Join::new(&header_record, join_path, other, args.on.as_deref().unwrap_or_default(), args.join_type)?)
+        },
+        None => None,
+    };
+    if let Some(ref join) = join {
+        header_record = join.headers(&header_record);
+    }
+    // --melt and --explode turn each row into several ones, also before the
+    // filters
+    let melt = match args.melt {
+        Some(ref melt_str) => Some(reshape::Melt::parse(melt_str, &header_record)?),
+        None => None,
+    };
+    if let Some(ref melt) = melt {
+        header_record = melt.headers(&header_record);
+    }
+    let explode = match args.explode {
+        Some(ref explode_str) => Some(reshape::Explode::parse(explode_str, &header_record)?),
+        None => None,
+    };
+    let headers = &header_record;
+
+    if args.info {
+        let json = args.format == output::Format::Json;
+        if !json {
+            println!("Number of columns: {}", headers.len());
+            if sniffed.is_some() {
+                println!("Sniffed delimiter: {:?}, quote: {:?}", dialect.delimiter as char, dialect.quote as char);
+            }
         }
-        println!("Number of columns: {}", n_cols);
 
+        // --estimate only reads the rows in the first bytes of the file. They
+        // are counted by lines, so with quoted fields with newlines the
+        // profile can read a few records past those bytes
+        let estimate = match args.estimate {
+            Some(sample_size) if files.len() <= 1 => Some(input::estimate_rows(first_path, sample_size, &reader_options)?
+                .ok_or("--estimate needs a plain CSV file, not stdin, a download, or a compressed or encoded file")?),
+            Some(_) => return Err("--estimate needs a single file".into()),
+            None => None,
+        };
+        let records: Box<dyn Iterator<Item = Result<StringRecord, Box<dyn Error>>>> = match estimate {
+            Some(ref estimate) => Box::new(records.take(estimate.sampled_rows)),
+            None => Box::new(records),
+        };
+
+        // Each column is profiled in the same pass that counts the rows
         let mut n_rows = 0;
-        for _ in rdr.records() {
+        let mut profile = profile::Profile::new(headers.len());
+        for result in records {
+            profile.update(&result?);
             n_rows += 1;
         }
-        println!("Number of rows: {}", n_rows);
-        
+        // When the sample was the whole file, the records were counted
+        let estimated = estimate.as_ref().is_some_and(|estimate| !estimate.exact);
+        if let Some(estimate) = estimate.filter(|estimate| !estimate.exact) {
+            n_rows = estimate.rows;
+        }
+        if json {
+            let info = serde_json::json!({
+                "files": if files.is_empty() { vec![String::from(input::STDIN_PATH)] } else { files.clone() },
+                "rows": n_rows,
+                "rows_estimated": estimated,
+                "sniffed": sniffed.map(|dialect| serde_json::json!({
+                    "delimiter": (dialect.delimiter as char).to_string(),
+                    "quote": (dialect.quote as char).to_string(),
+                })),
+                "columns": profile.json(headers),
+            });
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            return Ok(());
+        }
+        match estimated {
+            true => println!("Number of rows: ~{} (estimated from the first {} bytes)", n_rows, args.estimate.unwrap_or_default()),
+            false => println!("Number of rows: {}", n_rows),
+        }
+        println!("CSV columns:");
+        let options = output::Options { quote_style: args.quote_style, schema: Vec::new(), max_col_width: args.max_col_width, null_as: None };
+        let mut writer = output::new_writer(output::Format::Table, Box::new(BufWriter::new(io::stdout())), &options);
+        writer.write_header(&profile::PROFILE_HEADERS.map(String::from))?;
+        for row in profile.rows(headers) {
+            writer.write_row(&row)?;
+        }
+        writer.finish()?;
         return Ok(());
     }
 
     let mut col_idx_hashmap : HashMap::<String, usize> = HashMap::new();
-    let headers = rdr.headers().unwrap();
-    let mut header_idx : usize = 0;
-    for header in headers {
+    for (header_idx, header) in headers.iter().enumerate() {
         col_idx_hashmap.insert(header.to_string(), header_idx);
-        header_idx += 1;
+    }
+    // _row is a pseudo column with the 1-based row number, appended to the
+    // records seen by the filters. A real "_row" column takes precedence
+    let row_number_column = !col_idx_hashmap.contains_key(ROW_NUMBER_COLUMN);
+    if row_number_column {
+        col_idx_hashmap.insert(String::from(ROW_NUMBER_COLUMN), headers.len());
     }
     
     // It creates a hashmap column name -> column index
     // So it can be used later with the filters
+
     let mut col_indices: Vec<usize> = Vec::new();
-    if let Some(ref col_name) = cols {
+    if let Some(ref col_name) = args.cols {
         let col_names: Vec<&str> = col_name.split(',').collect();
-        let headers = rdr.headers().unwrap();
         col_indices = col_names.iter()
-            .map(|&name| headers.iter().position(|h| h == name).ok_or_else(|| "Column not found"))
+            .map(|&name| headers.iter().position(|h| h == name).ok_or("Column not found"))
             .collect::<Result<Vec<usize>, &str>>()?;
-        let mut print_index = 0;
-        for i in &col_indices {
-            print!("{}", headers[*i].to_string());
-            if print_index < col_indices.len() - 1 {
-                print!(",");
-            }
-            print_index += 1;
-        }
-        print!("\n");
+    } else if let Some(ref exclude_str) = args.exclude_cols {
+        let excluded = exclude_str.split(',')
+            .map(|name| columns::column_index(headers, name, "--exclude-cols"))
+            .collect::<Result<Vec<usize>, String>>()?;
+        col_indices = (0..headers.len()).filter(|i| !excluded.contains(i)).collect();
+    } else if args.agg.is_some() && args.group_by.is_none() {
+        // Plain aggregates use every column when none is selected
+        col_indices = (0..headers.len()).collect();
     }
 
-    // Creates the filter list
-    let mut _n_filters: Vec<RowFilter> = Vec::new();
-    if let Some(filters_str) = filters {
-        let _n_filter_strings: Vec<&str> = filters_str.split(',').collect();
+    // All the aggregates are computed in a single pass and printed as a CSV
+    // at the end. Without --group-by every row falls in the same group
+    let mut group_by = None;
+    if let Some(ref agg_str) = args.agg {
+        let key_columns = match args.group_by {
+            Some(ref group_by_str) => group_by_str.split(',')
+                .map(|name| columns::column_index(headers, name, "--group-by"))
+                .collect::<Result<Vec<usize>, String>>()?,
+            None => Vec::new(),
+        };
+        let specs = agg::AggSpec::parse_list(agg_str, headers, &col_indices)?;
+        group_by = Some(agg::GroupBy::new(key_columns, specs));
+    }
 
-        for filter_str in _n_filter_strings {
-            _n_filters.push(RowFilter::new(filter_str, col_idx_hashmap.clone()));
-        }
+    // The wide table of --pivot is printed at the end, once all the values
+    // of the columns column are known
+    let mut pivot = match args.pivot {
+        Some(ref pivot_str) => Some(reshape::Pivot::parse(pivot_str, headers)?),
+        None => None,
+    };
+
+    // --having uses the same filters, over the headers of the aggregated rows
+    let having = match (&args.having, &group_by) {
+        (Some(having_str), Some(group_by)) => {
+            let agg_col_idx_hashmap: HashMap<String, usize> = group_by.headers(headers).into_iter()
+                .enumerate()
+                .map(|(header_idx, header)| (header, header_idx))
+                .collect();
+            Some(FilterExpr::parse(having_str, &agg_col_idx_hashmap, args.date_format.as_deref())?)
+        },
+        _ => None,
+    };
+
+    // Running totals, appended as extra columns to each displayed row
+    let mut running = match args.running {
+        Some(ref running_str) => Some(agg::Running::new(agg::AggSpec::parse_list(running_str, headers, &[])?)),
+        None => None,
+    };
+
+    // Columns of the displayed rows, every column without --cols
+    let display_columns: Vec<usize> = match (&args.cols, &args.exclude_cols) {
+        (None, None) => (0..headers.len()).collect(),
+        _ => col_indices.clone(),
+    };
+
+    // --stats summarizes the displayed columns once all the rows are read
+    let mut describe = args.stats.then(|| profile::Describe::new(&display_columns));
+
+    let mut frequency = match args.freq {
+        Some(ref column_name) => Some(profile::Frequency::new(columns::column_index(headers, column_name, "--freq")?)),
+        None => None,
+    };
+
+    let mut correlation = match args.corr.as_deref() {
+        Some("") => Some(profile::Correlation::new((0..headers.len()).collect(), true)),
+        Some(corr_str) => Some(profile::Correlation::new(corr_str.split(',')
+            .map(|name| columns::column_index(headers, name, "--corr"))
+            .collect::<Result<Vec<usize>, String>>()?, false)),
+        None => None,
+    };
+    let mut duplicates = match (args.find_duplicates, &args.by) {
+        (true, Some(by_str)) => Some(unique::Duplicates::new(Some(by_str.split(',')
+            .map(|name| columns::column_index(headers, name, "--by"))
+            .collect::<Result<Vec<usize>, String>>()?))),
+        (true, None) => Some(unique::Duplicates::new(None)),
+        (false, _) => None,
+    };
+    let mut histogram = match args.hist {
+        Some(ref column_name) => Some(profile::Histogram::new(columns::column_index(headers, column_name, "--hist")?)),
+        None => None,
+    };
+
+    let mut outliers = match args.outliers {
+        Some(ref column_name) => {
+            let column = columns::column_index(headers, column_name, "--outliers")?;
+            Some(profile::Outliers::new(column, args.method, args.threshold))
+        },
+        None => None,
+    };
+    // The outliers are shown with their row numbers
+    let line_numbers = args.line_numbers || outliers.is_some();
+
+    let output_headers: Vec<String> = match (&group_by, &frequency, &duplicates) {
+        (Some(group_by), _, _) => group_by.headers(headers),
+        (None, Some(frequency), _) => frequency.headers(headers),
+        (None, None, Some(duplicates)) => duplicates.headers(headers),
+        (None, None, None) if args.stats => profile::DESCRIBE_HEADERS.map(String::from).to_vec(),
+        (None, None, None) => line_numbers.then(|| String::from(ROW_NUMBER_COLUMN)).into_iter()
+            .chain(display_columns.iter().map(|&i| String::from(&headers[i])))
+            .chain(running.iter().flat_map(agg::Running::headers))
+            .collect(),
+    };
+    let mut output_file = None;
+    let out: Box<dyn Write> = match args.output {
+        Some(ref path) => {
+            let (atomic_file, file) = output::AtomicFile::create(path)?;
+            output_file = Some(atomic_file);
+            Box::new(BufWriter::new(file))
+        },
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    let options = output::Options {
+        quote_style: args.quote_style,
+        schema: match args.schema {
+            Some(ref schema_str) => value::parse_schema(schema_str)?,
+            None => Vec::new(),
+        },
+        max_col_width: args.max_col_width,
+        null_as: args.null_as.clone(),
+    };
+    let mut writer = match args.to_sqlite {
+        Some(ref database) => {
+            let table = match args.table {
+                Some(ref table) => table.clone(),
+                None => first_path.filter(|&file| file != input::STDIN_PATH)
+                    .and_then(|file| Path::new(file).file_stem())
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| String::from("data")),
+            };
+            output::sqlite_writer(database, &table, &options)
+        },
+        None => output::new_writer(args.format, out, &options),
+    };
+    if args.shuffle {
+        writer = Box::new(sample::ShuffleWriter::new(writer, sample::new_rng(args.seed)));
     }
-        
+    if pivot.is_none() && histogram.is_none() && correlation.is_none() {
+        writer.write_header(&output_headers)?;
+    }
+    if args.follow {
+        writer.flush()?;
+    }
+
+    // Parses the filter expression
+    let filter = match args.filter {
+        Some(ref filters_str) => Some(FilterExpr::parse(filters_str, &col_idx_hashmap, args.date_format.as_deref())?),
+        None => None,
+    };
+
+    // --sort keeps the filtered rows until the input ends, and --offset and
+    // -n are applied to the sorted rows
+    let mut sorter = match args.sort {
+        Some(ref sort_str) => Some(sort::Sorter::new(sort::SortKey::parse_list(sort_str, headers)?, args.date_format.as_deref())),
+        None => None,
+    };
+    let display_row = |row_index: usize, record: &StringRecord, running: &mut Option<agg::Running>| {
+        let mut row: Vec<String> = line_numbers.then(|| (row_index + 1).to_string()).into_iter()
+            .chain(display_columns.iter().map(|&i| String::from(record.get(i).unwrap_or_default())))
+            .collect();
+        if let Some(ref mut running) = running {
+            row.extend(running.update(record));
+        }
+        row
+    };
+
+    // --unique and --unique-by drop the rows with a key seen before
+    let unique = match (args.unique, &args.unique_by) {
+        (_, Some(unique_by_str)) => Some(unique::Unique::new(Some(unique_by_str.split(',')
+            .map(|name| columns::column_index(headers, name, "--unique-by"))
+            .collect::<Result<Vec<usize>, String>>()?))),
+        (true, None) => Some(unique::Unique::new(None)),
+        (false, None) => None,
+    };
+
+    // --tail keeps the last rows until the input ends. Without anything
+    // needing the rows before them, they are read from the end of the file
+    let mut last_rows = args.tail.map(VecDeque::with_capacity);
+    let records: Box<dyn Iterator<Item = Result<StringRecord, Box<dyn Error>>>> = match args.tail {
+        Some(rows) if filter.is_none() && unique.is_none() && args.sample_frac.is_none() && !args.line_numbers && running.is_none()
+            && !pipeline.skips_rows() && join.is_none() && key_filter.is_none() && paste.is_none() && files.len() <= 1 => {
+            match input::tail(first_path, dialect, input_columns, rows, &reader_options)? {
+                Some(last_records) => last_records,
+                None => Box::new(records),
+            }
+        },
+        _ => Box::new(records),
+    };
+
+    let records: Box<dyn Iterator<Item = Result<StringRecord, Box<dyn Error>>>> = match paste {
+        Some(paste) => Box::new(paste.paste(records)),
+        None => records,
+    };
+
+    // Rows after --offset that pass the filter, with their index in the input
+    let skipped_rows = match sorter {
+        Some(_) => 0,
+        None => args.offset as usize,
+    };
+    let records = records.enumerate().skip(skipped_rows).filter_map(|(row_index, result)| match result {
+        Ok(record) => match pipeline.apply(record) {
+            Ok(Some(record)) => Some(Ok((row_index, record))),
+            Ok(None) => None,
+            Err(err) => Some(Err(format!("Row {}: {}", row_index + 1, err).into())),
+        },
+        Err(err) => Some(Err(err)),
+    });
+    let records = records.filter(|row| match (row, &key_filter) {
+        (Ok((_, record)), Some(key_filter)) => key_filter.accepts(record),
+        _ => true,
+    });
+    let records: Box<dyn Iterator<Item = input::IndexedRecord>> = match join {
+        Some(join) => Box::new(join.join(records)),
+        None => Box::new(records),
+    };
+    let records: Box<dyn Iterator<Item = input::IndexedRecord>> = match melt {
+        Some(ref melt) => Box::new(reshape::expand(records, |record| melt.rows(record))),
+        None => records,
+    };
+    let records: Box<dyn Iterator<Item = input::IndexedRecord>> = match explode {
+        Some(ref explode) => Box::new(reshape::expand(records, |record| explode.rows(record))),
+        None => records,
+    };
+    // The row number is only added to the record while the filter runs, and
+    // written in a buffer kept across the rows
+    let mut row_number = String::new();
+    let rows = records.filter_map(move |row| {
+        let (row_index, mut record) = match row {
+            Ok(row) => row,
+            Err(err) => return Some(Err(err)),
+        };
+        if let Some(ref filter) = filter {
+            if row_number_column {
+                row_number.clear();
+                write!(row_number, "{}", row_index + 1).expect("writing to a String");
+                record.push_field(&row_number);
+            }
+            let accepted = filter.accepts(&record);
+            if row_number_column {
+                record.truncate(record.len() - 1);
+            }
+            // With --invert-match only the rejected rows are displayed
+            if accepted == args.invert_match {
+                return None;
+            }
+        }
+        Some(Ok((row_index, record)))
+    });
+    let rows: Box<dyn Iterator<Item = input::IndexedRecord>> = match unique {
+        Some(unique) if args.keep == unique::Keep::Last => Box::new(unique.last_rows(rows)?.into_iter().map(Ok)),
+        Some(mut unique) => Box::new(rows.filter(move |row| row.as_ref().map_or(true, |(_, record)| unique.insert(record)))),
+        None => Box::new(rows),
+    };
+    // --sample-frac keeps each row with that probability, and --sample keeps
+    // a fixed number of rows until the input ends
+    let mut rng = sample::new_rng(args.seed);
+    let rows: Box<dyn Iterator<Item = input::IndexedRecord>> = match args.sample_frac {
+        Some(fraction) => Box::new(rows.filter(move |_| rng.random_bool(fraction))),
+        None => rows,
+    };
+    let mut reservoir = args.sample.map(|size| sample::Reservoir::new(size, sample::new_rng(args.seed)));
+
     let mut rows_processed : u32 = 0;
-    let mut rows_ignored : u32 = 0;
-    'records_loop: for result in rdr.records() {
-        if rows_ignored < offset {
-            rows_ignored += 1;
+    for row in rows {
+        let (row_index, record) = row?;
+
+        if let Some(ref mut group_by) = group_by {
+            group_by.update(&record);
+            continue;
+        }
+        if let Some(ref mut pivot) = pivot {
+            pivot.update(&record);
+            continue;
+        }
+        if let Some(ref mut describe) = describe {
+            describe.update(&record);
+            continue;
+        }
+        if let Some(ref mut frequency) = frequency {
+            frequency.update(&record);
             continue;
         }
-        // The iterator yields Result<StringRecord, Error>, so we check the
-        // error here.
-        let record = result?;
-
-        for filter in &_n_filters {
-            let cloned_record = record.clone();
-            if !filter.accepts(cloned_record) {
-                continue 'records_loop;
+        if let Some(ref mut histogram) = histogram {
+            histogram.update(&record);
+            continue;
+        }
+        if let Some(ref mut duplicates) = duplicates {
+            duplicates.insert(row_index, &record);
+            continue;
+        }
+        if let Some(ref mut outliers) = outliers {
+            outliers.update(row_index, record);
+            continue;
+        }
+        if let Some(ref mut correlation) = correlation {
+            correlation.update(&record);
+            continue;
+        }
+        if let Some(ref mut sorter) = sorter {
+            sorter.push(row_index, record)?;
+            continue;
+        }
+        if let Some(ref mut reservoir) = reservoir {
+            reservoir.push(row_index, record);
+            continue;
+        }
+        if let Some(ref mut last_rows) = last_rows {
+            if args.tail.is_some_and(|tail| last_rows.len() >= tail) {
+                last_rows.pop_front();
+            }
+            if args.tail != Some(0) {
+                last_rows.push_back((row_index, record));
             }
+            continue;
         }
 
-        match cols {
-            Some(_) => {
-                let mut print_index = 0;
-                for i in &col_indices {
-                    let col_value = record.get(*i).unwrap_or_default();
-                    print!("{}", col_value);
-                    if print_index < col_indices.len() - 1 {
-                        print!(",");
-                    }
-                    print_index += 1;
-                }
-                print!("\n");
-            },
-            None => println!("{:?}", record),
-        }
+        writer.write_row(&display_row(row_index, &record, &mut running))?;
 
+        if args.follow {
+            writer.flush()?;
+            continue;
+        }
         rows_processed += 1;
 
-        if rows_processed == max_rows {
+        if rows_processed == args.n {
             break;
         }
     }
+
+    let sampled_rows = reservoir.map(sample::Reservoir::into_rows);
+    for (row_index, record) in sampled_rows.into_iter().flatten().chain(last_rows.into_iter().flatten()) {
+        writer.write_row(&display_row(row_index, &record, &mut running))?;
+    }
+    if let Some(sorter) = sorter {
+        for row in sorter.into_sorted()?.skip(args.offset as usize).take(args.n as usize) {
+            let (row_index, record) = row?;
+            writer.write_row(&display_row(row_index, &record, &mut running))?;
+        }
+    }
+
+    if let Some(group_by) = group_by {
+        for row in group_by.rows() {
+            if let Some(ref having) = having {
+                if !having.accepts(&StringRecord::from(&row[..])) {
+                    continue;
+                }
+            }
+            writer.write_row(&row)?;
+        }
+    }
+    if let Some(describe) = describe {
+        for row in describe.rows(headers) {
+            writer.write_row(&row)?;
+        }
+    }
+    if let Some(frequency) = frequency {
+        for row in frequency.rows(args.top) {
+            writer.write_row(&row)?;
+        }
+    }
+    if let Some(histogram) = histogram {
+        for line in histogram.lines(args.bins as usize, profile::BAR_WIDTH) {
+            println!("{}", line);
+        }
+        if histogram.skipped() > 0 {
+            eprintln!("Skipped {} values that aren't numbers", histogram.skipped());
+        }
+    }
+    for (row_index, record) in outliers.map(profile::Outliers::rows).into_iter().flatten() {
+        writer.write_row(&display_row(row_index, &record, &mut running))?;
+    }
+    if let Some(duplicates) = duplicates {
+        for row in duplicates.rows() {
+            writer.write_row(&row)?;
+        }
+    }
+    if let Some(correlation) = correlation {
+        writer.write_header(&correlation.headers(headers))?;
+        for row in correlation.rows(headers) {
+            writer.write_row(&row)?;
+        }
+    }
+    if let Some(pivot) = pivot {
+        writer.write_header(&pivot.headers(headers))?;
+        for row in pivot.rows() {
+            writer.write_row(&row)?;
+        }
+    }
+    writer.finish()?;
+    // The writer is dropped first, so the file is complete when renamed
+    drop(writer);
+    if let Some(output_file) = output_file {
+        output_file.commit()?;
+    }
     Ok(())
+
 }
 
 // Example of use:
-// csvpeek <file> --info -> prints general info of the csv
-// csvpeek <file> -> prints the whole file (restricted by -n)
+// csvpeek <file> --info -> prints general info of the csv, and for each column its inferred type, the empty
+//   fields, distinct values (estimated past 100000), min, max, the longest and average length of its fields
+//   in characters and bytes, to size VARCHAR columns, and a few example values.
+// csvpeek big.csv --info --estimate [64MB] -> estimates the rows from the size of the file and the first 8 MB,
+//   where the columns are profiled, instead of reading all of it.
+// csvpeek <file> --info --format json -> the same info as a JSON object, with the files, rows, sniffed dialect
+//   and an object per column, for scripts.
+// csvpeek diff old.csv new.csv [--rows] -> counts the rows added, removed and unchanged, matching whole rows.
+//   --rows also prints the added and removed ones. The columns can be in another order in new.csv.
+// csvpeek diff old.csv new.csv --key id -> matches the rows by id and prints what changed in each, like
+//   "id=42: price 10.0 -> 12.5", and the ids added and removed.
+// csvpeek schema-diff old.csv new.csv -> the columns added, removed and moved, and the ones whose inferred type
+//   changed, like "type: price int -> float". The types come from the first 1000 rows, or --infer-rows.
+// csvpeek split sales.csv --chunk-rows 100000 -> writes sales-1.csv, sales-2.csv... each with the header.
+//   --chunk-size 10MB splits by size, and --by region writes sales-north.csv and so on. --template names them.
+// csvpeek except a.csv b.csv [--key id] -> the rows of a.csv not in b.csv, each once. intersect prints the ones
+//   in both files, and union the rows of both. Only the keys are kept in memory.
+// zcat big.csv.gz | csvpeek - --cols a,b -> reads the csv from stdin, also when no file is given.
+// csvpeek jan.csv feb.csv mar.csv --filter "status=error" -> reads several files with the same headers as one.
+// csvpeek 'logs/2024-*.csv' -> expands glob patterns itself, in alphabetical order.
+// csvpeek https://example.com/data.csv --cols id,name -n 20 -> streams the file from an HTTP(S) URL.
+// csvpeek s3://bucket/exports/sales.csv.gz -n 5 -> streams an S3 object, when built with --features s3.
+//   Credentials and region come from the usual AWS environment variables and config files.
+// csvpeek events.jsonl --cols id,type -> reads JSON Lines, with a column for each top level key of the
+//   first 1000 lines. Nested values are kept as JSON. --input-format jsonl does it for any file and stdin.
+// csvpeek data.parquet --cols id,price --filter "price>10" -> reads parquet files, with nulls as empty fields.
+// csvpeek report.xlsx --sheet Sales -n 5 -> reads a sheet of an Excel workbook, the first one by default.
+// csvpeek accounts.txt --fwf "0-9,10-29,30-39" -> splits fixed-width lines into columns by character ranges,
+//   both ends included, and trims the fields. --fwf columns.txt reads "name start-end" lines instead.
+// csvpeek app.log.csv --follow --filter "level=ERROR" -> prints the matching rows as they are appended,
+//   like tail -f, until interrupted with Ctrl-C.
+// csvpeek <file> --flexible -> reads ragged rows instead of failing, padding the short ones with empty
+//   fields and dropping the extra fields of the long ones.
+// csvpeek <file> --skip-lines 3 -> ignores the first 3 lines, for exports with metadata before the header.
+// csvpeek <file> --encoding windows-1252 -> reads files that are not UTF-8, like latin1 or utf-16le.
+// csvpeek <file> --comment '#' -> ignores the lines starting with #.
+// csvpeek <file> --no-header --cols c1,c3 -> the first row is data, columns are named c1, c2...
+// csvpeek data.csv -d ';' -> reads files separated by ';', '|', '\t' or any other single character.
+//   .tsv and .tab files are read with tabs by default.
+// csvpeek export.txt --sniff -> guesses the delimiter and quote from the first 8 KB, shown by --info.
+//   Done by default for stdin and files that are not .csv, .tsv or .tab.
+// csvpeek big.csv.gz -> decompresses gzip files. --gzip does it for any file and stdin.
+//   Also .zst, .bz2 and .xz files, or any input with --compression zstd|bzip2|xz|gzip|none.
+// csvpeek <file> -> prints the whole file as CSV (restricted by -n)
+// csvpeek <file> --rename "amt=amount,cust=customer" --filter "amount>10" -> renames columns in the output
+//   header. --cols, --filter and the other options use the new names.
 // csvpeek <file> --cols col1,col2,col3 -> shows the data but only for certain columns
+// csvpeek <file> --derive "total=price*qty" --derive "tax=total*0.21" -> appends computed columns, usable
+//   by --cols, --filter and the rest. Fields that are not numbers leave the result empty.
+// csvpeek <file> --transform "city=trim|upper,name=title" -> normalizes values before the filters and the
+//   output with lower, upper, trim, title or squish (trim and collapse inner spaces).
+// csvpeek <file> --date "created_at:%d/%m/%Y->%Y-%m-%d" -> reformats dates, also read in the ISO formats.
+//   Values that don't parse are kept, so a repeated --date can fix a column with mixed formats.
+// csvpeek <file> --replace "phone:s/[^0-9]//g" -> sed-style regex substitution on a column, on every column
+//   without "phone:". The replacement can use \1 and &, and the flags are g and i.
+// csvpeek <file> --split-col "full_name: :first,last" -> appends the parts of a column split on a delimiter.
+// csvpeek <file> --merge-cols "address=street+', '+city" -> appends a column joining others with quoted texts.
+// csvpeek <file> --fill-na "country=unknown" [--fill-na-all 0] -> replaces empty values of a column, or of all.
+// csvpeek <file> --cast "amount:float,id:int,flag:bool" [--cast-error error|skip|blank] -> normalizes values to a
+//   type, stopping at a value that doesn't fit, leaving its row out or emptying it.
+// csvpeek <file> --exclude-cols notes,raw_json -> shows every column but those.
 // csvpeek <file> -n N -> shows up to N rows
 // csvpeek <file> --offset M -> Ignore first M rows
 // csvpeek <file> --filter "image_number<3" -> applies different filters: <, >, = (string equality) and == (numeric equality).
-// Features not implemented yet:
-// csvpeek <file> --cols col1 --agg sum -> does an aggregate of the columns.
-// Agregates: sum, stdp, stds, avg, count
+//   Comparisons are numeric when both sides are numbers, by date when both are dates, and by string otherwise.
+// csvpeek <file> --filter "price>=10.5" -> range filters with >= and <=.
+// csvpeek <file> --filter "status!=failed" -> excludes rows, comparing numerically when both sides are numbers.
+// csvpeek <file> --filter "name~^img_[0-9]+\.png$" -> keeps rows whose column matches a regex.
+// csvpeek <file> --filter "extension~=png" -> case insensitive string equality.
+// csvpeek <file> --filter "filename like *.png" -> shell style globs with *, ? and [...].
+// csvpeek <file> --filter "city fuzzy:2 Chcago" -> values within a Levenshtein distance (2 without ":N").
+// csvpeek <file> --filter "email notempty" -> keeps rows with a value in the column. Use isempty for the opposite.
+// csvpeek <file> --filter "(status=ok OR status=warn) AND size>1000" -> combines filters with AND, OR and parentheses.
+//   Filters separated by commas must all match, like with AND.
+// csvpeek <file> --filter "NOT name~temp" -> negates any filter. "!name~temp" works too.
+// csvpeek <file> --filter "created_at>2024-01-01" [--date-format "%d/%m/%Y"] -> compares dates when both sides are dates.
+// csvpeek <file> --filter "end_time>start_time" -> compares two columns when the right side is a header name.
+// csvpeek <file> --filter 'title="a=b, c"' -> quoted values can hold operators, commas and spaces. Escape quotes with \".
+// csvpeek <file> --filter "_row between 50 and 60" -> _row is the 1-based row number, counted before --offset.
+//   "x between a and b" is the same as "x>=a AND x<=b".
+//   Column names with spaces or operator characters are quoted the same way: '"page title"=home'.
+// csvpeek <file> --filter "status=ok" -v -> shows the rows rejected by the filter, like grep -v.
+// csvpeek <file> --filter "status=failed" --line-numbers -> adds a first _row column with the row number
+//   in the file, before --offset and --filter, to find the rows again.
+// csvpeek <file> --sort size:desc -n 10 -> the 10 biggest rows. --offset and -n apply after sorting, and
+//   several columns break ties: --sort "day,amount:desc". Numbers and dates are compared as such.
+//   Big inputs are sorted in chunks of 256 MB spilled to temporary files, so memory stays bounded.
+// csvpeek big.log.csv --tail 20 -> the last 20 rows, read from the end of the file without scanning it.
+//   With --filter the whole file is read, keeping the last matching rows.
+// csvpeek <file> --find-duplicates [--by email] -> each repeated row, or email, with its count and row numbers.
+// csvpeek <file> --unique -> drops repeated rows. --unique-by id,day only compares those columns, and
+//   --keep last keeps the last row of each key instead of the first one.
+// csvpeek <file> --sample 100 [--seed 42] -> 100 rows chosen at random, holding only 100 rows in memory.
+//   --sample-frac 0.01 keeps about 1% of the rows instead, still limited by -n.
+// csvpeek <file> --shuffle -n 1000000 [--seed 7] -> the rows in a random order, e.g. for train/validation splits.
+//   Only the displayed rows are shuffled, so raise -n to shuffle the whole file.
+// csvpeek <file> --cols col1 --agg sum -> does an aggregate of the filtered rows, ignoring -n.
+// Agregates: sum, avg, min, max, count, stdp (population standard deviation),
+//   stds (sample standard deviation) and var (sample variance)
+// csvpeek <file> --group-by region --agg sum:amount,count -> one row per region with the aggregates.
+//   Plain "count" counts the rows of each group. --group-by accepts several columns.
+// csvpeek <file> --agg count_distinct --cols city -> number of unique values. approx_count_distinct
+//   estimates it with a HyperLogLog sketch for huge files.
+// csvpeek <file> --agg mode:city -> the most frequent value and its count, like "paris (3)".
+// csvpeek <file> --agg sum:amount,avg:price,max:qty -> several aggregates computed in a single pass.
+// csvpeek <file> --stats [--cols price,qty] -> the count, distinct values, mean, std, min, quartiles and max of
+//   each column, like describe() of pandas. Text columns only get the count and distinct values.
+// csvpeek <file> --freq city [--top 20] -> each city with its count and percentage of the rows, the most common first.
+// csvpeek <file> --corr price,qty -> the matrix of Pearson correlations between the columns, or between every
+//   numeric column with a plain --corr.
+// csvpeek <file> --outliers temp [--method iqr] [--threshold 2] -> the rows whose temp is more than 3 standard
+//   deviations from the mean, or 1.5 interquartile ranges out of the quartiles, with their row numbers.
+// csvpeek <file> --hist price [--bins 20] -> a histogram of the prices drawn with bars in the terminal.
+// csvpeek <file> --pivot "index=date, columns=region, values=sales, agg=sum" -> a row per date and a column
+//   per region, with the aggregate of the sales of each. The rows are read until the end, ignoring -n.
+// csvpeek orders.csv --join customers.csv --on customer_id=id -> each order followed by the columns of its
+//   customers, leaving out the orders without one. Columns named like one of orders.csv get a customers_ prefix.
+// csvpeek orders.csv --join customers.csv --on customer_id=id --join-type left --filter "city isempty" -> the orders
+//   without a customer. right also keeps the customers without orders, at the end, and full keeps both.
+// csvpeek names.csv --paste emails.csv -> each row followed by the row in the same position of emails.csv,
+//   an error when one file has more rows, unless --pad fills them in with empty fields.
+// csvpeek users.csv --semi-join allowed.csv --on id -> the rows with an id found in allowed.csv, and
+//   --anti-join banned.csv the ones without. Only the keys of the other file are kept in memory.
+// csvpeek <file> --melt "id_cols=date, value_cols=us,eu,apac" -> a row per date and region, with the columns
+//   date, variable and value, seen as such by the filters and the other options.
+// csvpeek <file> --explode "tags:|" -> a row for each of the tags of a row, so they can be filtered and
+//   aggregated one by one, e.g. with --group-by tags --agg count.
+// csvpeek <file> --group-by region --agg sum:amount --having "sum_amount>1000" -> filters the aggregated rows,
+//   with the same syntax as --filter over the aggregate headers.
+// csvpeek <file> --cols day,amount --running sum:amount -> appends the running total to each row.
+// csvpeek <file> --format json -> prints the rows as a JSON array of objects keyed by header name.
+//   --format jsonl prints one object per line instead.
+// csvpeek <file> --format yaml -> prints the rows as a YAML sequence of mappings, with string values.
+// csvpeek <file> --format table -> prints an aligned table with borders.
+// csvpeek <file> --format vertical -> one "column: value" line per field, like \G in MySQL.
+// csvpeek <file> --format plain [--max-col-width 20] -> columns of a fixed width, cutting longer values with "…".
+// csvpeek <file> --format markdown -> prints a GitHub flavored markdown table, ready to paste.
+// csvpeek <file> --format html -> prints an HTML <table> with the headers in <thead>.
+// csvpeek <file> --output out.json --format json -> writes to a file, replaced only when everything was written.
+//   -o is already taken by --offset, so --output has no short form.
+// csvpeek <file> --null-as NULL -> shows empty fields as NULL. Parquet, arrow, xlsx and sqlite keep real nulls.
+// csvpeek <file> --quote-style always -> quotes every CSV field. Default "necessary", "never" never quotes.
+// csvpeek <file> --format parquet --output out.parquet [--schema "zip:text"] -> writes a parquet file. Column
+//   types are int, float or text, inferred from the values unless given by --schema.
+// csvpeek <file> --to-sqlite db.sqlite [--table sales] -> creates a table with the rows, named after the file
+//   by default ("data" for stdin). The column types are the same as for parquet.
+// csvpeek <file> --format xlsx --output out.xlsx -> writes an Excel workbook, with numeric cells for the
+//   int and float columns.
+// csvpeek <file> --format arrow > rows.arrow -> writes an Arrow IPC stream, typed like parquet, for loading
+//   into Polars, DataFusion or pandas.
 fn main() {
     let args = Args::parse();
 
-    let csv = args.file;
-
-    if let Err(err) = read_csv(&csv, args.cols, args.offset, args.n, args.info, args.filter) {
+    let result = match args.command {
+        Some(Command::Diff(ref diff_args)) => {
+            diff::run(&diff_args.old, &diff_args.new, diff_args.rows, diff_args.key.as_deref(), &reader_options(&args))
+        },
+        Some(Command::SchemaDiff(ref schema_args)) => {
+            diff::run_schema(&schema_args.old, &schema_args.new, schema_args.infer_rows, &reader_options(&args))
+        },
+        Some(Command::Split(ref split_args)) => {
+            let mode = match (split_args.chunk_rows, split_args.chunk_size, &split_args.by) {
+                (Some(rows), _, _) => split::SplitMode::Rows(rows),
+                (_, Some(size), _) => split::SplitMode::Size(size),
+                (_, _, by) => split::SplitMode::Column(by.clone().unwrap_or_default()),
+            };
+            split::run(&split_args.file, &mode, split_args.template.as_deref(), &reader_options(&args))
+        },
+        Some(Command::Union(ref set_args)) => run_set_operation(setops::SetOperation::Union, set_args, &args),
+        Some(Command::Intersect(ref set_args)) => run_set_operation(setops::SetOperation::Intersect, set_args, &args),
+        Some(Command::Except(ref set_args)) => run_set_operation(setops::SetOperation::Except, set_args, &args),
+        None => read_csv(&args),
+    };
+    if let Err(err) = result {
         println!("Error reading or processing CSV: {}", err);
         process::exit(1);
     }
 }
 
 #[test]
-fn test_equal_row_filter_constructor() {
-    let mut hash_map = HashMap::<String, usize>::new();
-    hash_map.insert(String::from("IMAGE_NAME"), 2);
-    let row_filter = RowFilter::new("IMAGE_NAME=file1.png", hash_map);
-
-    // The operator should be EqualString
-    assert!(row_filter.operator == RowFilterOperator::EqualString);
-    assert_eq!(row_filter.left_column, Some(2));
-    assert_eq!(row_filter.right_column, None);
-    assert_eq!(row_filter.left_value, None);
-    assert_eq!(row_filter.right_value, Some(String::from("file1.png")));
-}
-
-#[test]
-fn test_equal_row_filter_accepts_method() {
-    let mut hash_map = HashMap::<String, usize>::new();
-    hash_map.insert(String::from("IMAGE_NAME"), 2);
-    let row_filter = RowFilter::new("IMAGE_NAME=file1.png", hash_map);
-
-    let record = StringRecord::from(vec!["someContentInFirstColumn", "someContentInSecondColumn", "file1.png"]);
-
-    let record2 = StringRecord::from(vec!["someContentInFirstColumn", "someContentInSecondColumn", "file2.png"]);
-
-    // First record should be accepted
-    assert!(row_filter.accepts(record));
-    // First record should not be accepted
-    assert!(!row_filter.accepts(record2));
+fn test_hist_conflicts() {
+    // The bars are printed as they are, so nothing else can format them
+    assert!(Args::try_parse_from(["csvpeek", "sales.csv", "--hist", "price"]).is_ok());
+    assert!(Args::try_parse_from(["csvpeek", "sales.csv", "--hist", "price", "--format", "json"]).is_err());
+    assert!(Args::try_parse_from(["csvpeek", "sales.csv", "--hist", "price", "--to-sqlite", "x.db"]).is_err());
 }
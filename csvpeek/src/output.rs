@@ -0,0 +1,745 @@
+mod arrow;
+mod parquet;
+mod sqlite;
+mod xlsx;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::value::ColumnType;
+
+/// Output formats accepted by --format
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+    Jsonl,
+    Table,
+    Markdown,
+    Html,
+    Parquet,
+    Xlsx,
+    Arrow,
+    Yaml,
+    Plain,
+    Vertical,
+}
+
+/// When the csv format quotes the fields, for --quote-style
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum QuoteStyle {
+    Always,
+    /// Only fields with delimiters, quotes or newlines
+    Necessary,
+    Never,
+}
+
+/// Settings of the writers, each format uses the ones that apply to it
+pub struct Options {
+    pub quote_style: QuoteStyle,
+    /// Column types of the typed formats, inferred for the missing columns
+    pub schema: Vec<(String, ColumnType)>,
+    /// Width of every column of the plain format
+    pub max_col_width: usize,
+    /// Placeholder shown instead of the empty fields
+    pub null_as: Option<String>,
+}
+
+/// Destination of the displayed rows. The header is written once, before
+/// any row, and finish is called after the last one
+pub trait RowWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>>;
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>>;
+    fn finish(&mut self) -> Result<(), Box<dyn Error>>;
+
+    /// Writes out the rows written so far, for --follow. The formats that
+    /// need every row before writing anything do nothing
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+pub fn new_writer(format: Format, out: Box<dyn Write>, options: &Options) -> Box<dyn RowWriter> {
+    let writer = format_writer(format, out, options);
+    match options.null_as {
+        // The typed formats already write the empty fields as real nulls
+        Some(ref null_as) if !matches!(format, Format::Parquet | Format::Xlsx | Format::Arrow) => {
+            Box::new(NullAsWriter { writer, null_as: null_as.clone() })
+        },
+        _ => writer,
+    }
+}
+
+fn format_writer(format: Format, out: Box<dyn Write>, options: &Options) -> Box<dyn RowWriter> {
+    match format {
+        Format::Csv => Box::new(CsvWriter::new(out, options.quote_style)),
+        Format::Json => Box::new(JsonWriter { out, headers: Vec::new(), rows_written: 0 }),
+        Format::Jsonl => Box::new(JsonLinesWriter { out, headers: Vec::new() }),
+        Format::Table => Box::new(TableWriter { out, headers: Vec::new(), rows: Vec::new() }),
+        Format::Markdown => Box::new(MarkdownWriter { out }),
+        Format::Yaml => Box::new(YamlWriter { out, headers: Vec::new(), rows_written: 0 }),
+        Format::Plain => Box::new(PlainWriter { out, width: options.max_col_width }),
+        Format::Vertical => Box::new(VerticalWriter { out, headers: Vec::new(), rows_written: 0 }),
+        Format::Html => Box::new(HtmlWriter { out }),
+        Format::Parquet => Box::new(parquet::ParquetWriter {
+            out,
+            schema: options.schema.clone(),
+            headers: Vec::new(),
+            rows: Vec::new(),
+        }),
+        Format::Xlsx => Box::new(xlsx::XlsxWriter {
+            out,
+            schema: options.schema.clone(),
+            headers: Vec::new(),
+            rows: Vec::new(),
+        }),
+        Format::Arrow => Box::new(arrow::ArrowWriter {
+            out,
+            schema: options.schema.clone(),
+            headers: Vec::new(),
+            rows: Vec::new(),
+        }),
+    }
+}
+
+/// Replaces the empty fields with a placeholder before passing the rows on,
+/// for --null-as
+pub struct NullAsWriter {
+    writer: Box<dyn RowWriter>,
+    null_as: String,
+}
+
+impl RowWriter for NullAsWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.writer.write_header(headers)
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        let row: Vec<String> = row.iter()
+            .map(|field| if field.is_empty() { self.null_as.clone() } else { field.clone() })
+            .collect();
+        self.writer.write_row(&row)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.finish()
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()
+    }
+}
+
+/// Writer that creates a table in a SQLite database, for --to-sqlite
+pub fn sqlite_writer(path: &str, table: &str, options: &Options) -> Box<dyn RowWriter> {
+    Box::new(sqlite::SqliteWriter {
+        path: String::from(path),
+        table: String::from(table),
+        schema: options.schema.clone(),
+        headers: Vec::new(),
+        rows: Vec::new(),
+    })
+}
+
+/// Column types of the typed formats: the ones in the schema, and the
+/// inferred ones for the rest
+fn column_types(headers: &[String], rows: &[Vec<String>],
+                schema: &[(String, ColumnType)]) -> Result<Vec<ColumnType>, String> {
+    if let Some((column, _)) = schema.iter().find(|(column, _)| !headers.contains(column)) {
+        return Err(format!("Unknown column '{}' in --schema", column));
+    }
+    Ok(headers.iter().enumerate()
+        .map(|(i, header)| match schema.iter().find(|(column, _)| column == header) {
+            Some(&(_, column_type)) => column_type,
+            None => ColumnType::infer(rows.iter().map(|row| cell(row, i))),
+        })
+        .collect())
+}
+
+fn cell(row: &[String], i: usize) -> &str {
+    row.get(i).map(String::as_str).unwrap_or_default()
+}
+
+/// Parses a field of a typed column, empty fields are nulls
+fn typed_value<T>(header: &str, field: &str, type_name: &str,
+                  parse: fn(&str) -> Option<T>) -> Result<Option<T>, String> {
+    match field.trim() {
+        "" => Ok(None),
+        _ => parse(field).map(Some)
+            .ok_or_else(|| format!("Value '{}' of column '{}' is not {}", field, header, type_name)),
+    }
+}
+
+/// Temporary file next to the --output path, moved over it by commit once
+/// everything was written. A failed run leaves any previous file untouched,
+/// and the temporary file is removed when dropped without a commit
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: &str) -> io::Result<(Self, File)> {
+        let path = PathBuf::from(path);
+        let file_name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid output path '{}'", path.display())))?;
+        let temp_name = format!(".{}.csvpeek-{}.tmp", file_name.to_string_lossy(), std::process::id());
+        let temp_path = path.parent().unwrap_or(Path::new("")).join(temp_name);
+        let file = File::create(&temp_path)?;
+        Ok((Self { path, temp_path, committed: false }, file))
+    }
+
+    pub fn commit(mut self) -> io::Result<()> {
+        fs::rename(&self.temp_path, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// CSV through csv::Writer, so fields with commas, quotes or newlines are
+/// still valid CSV
+pub struct CsvWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+}
+
+impl CsvWriter {
+    fn new(out: Box<dyn Write>, quote_style: QuoteStyle) -> Self {
+        let quote_style = match quote_style {
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        };
+        Self { writer: csv::WriterBuilder::new().quote_style(quote_style).from_writer(out) }
+    }
+}
+
+impl RowWriter for CsvWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.writer.write_record(headers)?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.writer.write_record(row)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Array of objects keyed by header name, one object per line. Values are
+/// kept as strings so nothing like leading zeros gets lost
+pub struct JsonWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows_written: usize,
+}
+
+impl RowWriter for JsonWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        let separator = if self.rows_written == 0 { "[\n" } else { ",\n" };
+        write!(self.out, "{}  {}", separator, json_object(&self.headers, row)?)?;
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.rows_written {
+            0 => writeln!(self.out, "[]")?,
+            _ => writeln!(self.out, "\n]")?,
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// JSON Lines: one object per row, written as the rows come
+pub struct JsonLinesWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+}
+
+impl RowWriter for JsonLinesWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{}", json_object(&self.headers, row)?)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Aligned table with borders. The rows are kept until finish, since every
+/// row is needed to know the width of the columns
+pub struct TableWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl RowWriter for TableWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let widths = column_widths(&self.headers, &self.rows);
+        let border: Vec<String> = widths.iter().map(|&width| "-".repeat(width + 2)).collect();
+        let border = format!("+{}+", border.join("+"));
+
+        writeln!(self.out, "{}", border)?;
+        writeln!(self.out, "| {} |", pad_cells(&self.headers, &widths).join(" | "))?;
+        writeln!(self.out, "{}", border)?;
+        for row in &self.rows {
+            writeln!(self.out, "| {} |", pad_cells(row, &widths).join(" | "))?;
+        }
+        if !self.rows.is_empty() {
+            writeln!(self.out, "{}", border)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// GitHub flavored markdown table
+pub struct MarkdownWriter {
+    out: Box<dyn Write>,
+}
+
+impl RowWriter for MarkdownWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{}", markdown_row(headers))?;
+        writeln!(self.out, "|{}", " --- |".repeat(headers.len()))?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "{}", markdown_row(row))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Pipes would end the cell and newlines the row, so both are escaped
+fn markdown_row(cells: &[String]) -> String {
+    let cells: Vec<String> = cells.iter()
+        .map(|cell| cell.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>"))
+        .collect();
+    format!("| {} |", cells.join(" | "))
+}
+
+/// HTML table, with the header row in <thead> and the rows in <tbody>
+pub struct HtmlWriter {
+    out: Box<dyn Write>,
+}
+
+impl RowWriter for HtmlWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "<table>")?;
+        writeln!(self.out, "  <thead>")?;
+        writeln!(self.out, "    <tr>{}</tr>", html_cells("th", headers))?;
+        writeln!(self.out, "  </thead>")?;
+        writeln!(self.out, "  <tbody>")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "    <tr>{}</tr>", html_cells("td", row))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        writeln!(self.out, "  </tbody>")?;
+        writeln!(self.out, "</table>")?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn html_cells(tag: &str, cells: &[String]) -> String {
+    cells.iter().map(|cell| format!("<{tag}>{}</{tag}>", html_escape(cell))).collect()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Columns of a fixed width, so the rows can be written as they come. Longer
+/// cells are cut with an ellipsis
+pub struct PlainWriter {
+    out: Box<dyn Write>,
+    width: usize,
+}
+
+impl PlainWriter {
+    fn write_line(&mut self, cells: &[String]) -> Result<(), Box<dyn Error>> {
+        let cells: Vec<String> = cells.iter().map(|cell| fit_width(cell, self.width)).collect();
+        writeln!(self.out, "{}", cells.join("  ").trim_end())?;
+        Ok(())
+    }
+}
+
+impl RowWriter for PlainWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.write_line(headers)
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.write_line(row)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Pads or truncates a cell to exactly width characters. Newlines and tabs
+/// become spaces so they don't break the alignment
+fn fit_width(cell: &str, width: usize) -> String {
+    let cell: String = cell.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    if cell.chars().count() <= width {
+        return format!("{:<width$}", cell);
+    }
+    let truncated: String = cell.chars().take(width.saturating_sub(1)).collect();
+    format!("{}…", truncated)
+}
+
+/// One "column: value" line per field, with a numbered separator line
+/// before each row, like \G in MySQL. Handy for rows with many columns
+pub struct VerticalWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows_written: usize,
+}
+
+impl RowWriter for VerticalWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows_written += 1;
+        let stars = "*".repeat(27);
+        writeln!(self.out, "{} {}. row {}", stars, self.rows_written, stars)?;
+        let width = self.headers.iter().map(|header| header.chars().count()).max().unwrap_or_default();
+        for (i, header) in self.headers.iter().enumerate() {
+            writeln!(self.out, "{:>width$}: {}", header, cell(row, i))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// YAML sequence with one mapping per row. Like with json the values are all
+/// strings, so the ones YAML would read as something else are quoted
+pub struct YamlWriter {
+    out: Box<dyn Write>,
+    headers: Vec<String>,
+    rows_written: usize,
+}
+
+impl RowWriter for YamlWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        for (i, header) in self.headers.iter().enumerate() {
+            let indent = if i == 0 { "- " } else { "  " };
+            writeln!(self.out, "{}{}: {}", indent, yaml_scalar(header), yaml_scalar(cell(row, i)))?;
+        }
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.rows_written == 0 {
+            writeln!(self.out, "[]")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Plain scalar when it can only be read back as the same string, double
+/// quoted otherwise. Double quoted YAML has the same escapes as JSON
+fn yaml_scalar(text: &str) -> String {
+    const RESERVED: [&str; 11] = ["true", "false", "yes", "no", "on", "off", "y", "n", "null", "~", "nan"];
+    let plain = text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || " _-./".contains(c))
+        && !text.ends_with(' ')
+        && !RESERVED.contains(&text.to_lowercase().as_str());
+    match plain {
+        true => String::from(text),
+        false => serde_json::to_string(text).unwrap_or_default(),
+    }
+}
+
+/// Width in characters of the widest cell of each column
+fn column_widths(headers: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    widths
+}
+
+/// Left aligned cells, padded to the width of their column
+fn pad_cells(cells: &[String], widths: &[usize]) -> Vec<String> {
+    widths.iter().enumerate()
+        .map(|(i, &width)| format!("{:<width$}", cells.get(i).map(String::as_str).unwrap_or_default()))
+        .collect()
+}
+
+/// Single line JSON object, with the keys in the order of the headers
+fn json_object(headers: &[String], row: &[String]) -> Result<String, Box<dyn Error>> {
+    let mut members = Vec::new();
+    for (header, value) in headers.iter().zip(row) {
+        members.push(format!("{}:{}", serde_json::to_string(header)?, serde_json::to_string(value)?));
+    }
+    Ok(format!("{{{}}}", members.join(",")))
+}
+
+// Output shared with the test, since the writers own theirs
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn render(format: Format, headers: &[&str], rows: &[&[&str]]) -> String {
+    String::from_utf8(render_bytes(format, &test_options(), headers, rows)).unwrap()
+}
+
+#[cfg(test)]
+fn test_options() -> Options {
+    Options { quote_style: QuoteStyle::Necessary, schema: Vec::new(), max_col_width: 20, null_as: None }
+}
+
+#[cfg(test)]
+fn render_bytes(format: Format, options: &Options, headers: &[&str], rows: &[&[&str]]) -> Vec<u8> {
+    let buffer = SharedBuffer::default();
+    let mut writer = new_writer(format, Box::new(buffer.clone()), options);
+    let to_strings = |fields: &[&str]| fields.iter().map(|field| String::from(*field)).collect::<Vec<String>>();
+    writer.write_header(&to_strings(headers)).unwrap();
+    for row in rows {
+        writer.write_row(&to_strings(row)).unwrap();
+    }
+    writer.finish().unwrap();
+    let rendered = buffer.0.borrow().clone();
+    rendered
+}
+
+#[test]
+fn test_csv_output() {
+    assert_eq!(render(Format::Csv, &["a", "b"], &[&["1", "x"], &["2", "y"]]), "a,b\n1,x\n2,y\n");
+    assert_eq!(render(Format::Csv, &["a", "b"], &[&["x, y", "say \"hi\""]]), "a,b\n\"x, y\",\"say \"\"hi\"\"\"\n");
+}
+
+#[test]
+fn test_csv_quote_style() {
+    let always = Options { quote_style: QuoteStyle::Always, ..test_options() };
+    assert_eq!(render_bytes(Format::Csv, &always, &["a"], &[&["1"]]), b"\"a\"\n\"1\"\n");
+    let never = Options { quote_style: QuoteStyle::Never, ..test_options() };
+    assert_eq!(render_bytes(Format::Csv, &never, &["a", "b"], &[&["x, y", "z"]]), b"a,b\nx, y,z\n");
+}
+
+#[test]
+fn test_json_output() {
+    assert_eq!(render(Format::Json, &["id", "name"], &[&["007", "a \"b\""], &["2", ""]]),
+               "[\n  {\"id\":\"007\",\"name\":\"a \\\"b\\\"\"},\n  {\"id\":\"2\",\"name\":\"\"}\n]\n");
+    assert_eq!(render(Format::Json, &["id"], &[]), "[]\n");
+}
+
+#[test]
+fn test_json_lines_output() {
+    assert_eq!(render(Format::Jsonl, &["id", "name"], &[&["1", "a"], &["2", "b"]]),
+               "{\"id\":\"1\",\"name\":\"a\"}\n{\"id\":\"2\",\"name\":\"b\"}\n");
+    assert_eq!(render(Format::Jsonl, &["id"], &[]), "");
+}
+
+#[test]
+fn test_table_output() {
+    assert_eq!(render(Format::Table, &["city", "n"], &[&["Zürich", "10"], &["Rome", "7"]]),
+               "+--------+----+\n\
+                | city   | n  |\n\
+                +--------+----+\n\
+                | Zürich | 10 |\n\
+                | Rome   | 7  |\n\
+                +--------+----+\n");
+}
+
+#[test]
+fn test_markdown_output() {
+    assert_eq!(render(Format::Markdown, &["cmd", "note"], &[&["a|b", "line 1\nline 2"]]),
+               "| cmd | note |\n| --- | --- |\n| a\\|b | line 1<br>line 2 |\n");
+}
+
+#[test]
+fn test_html_output() {
+    let expected = [
+        "<table>",
+        "  <thead>",
+        "    <tr><th>name</th><th>size</th></tr>",
+        "  </thead>",
+        "  <tbody>",
+        "    <tr><td>&lt;b&gt;&amp;co&lt;/b&gt;</td><td>1</td></tr>",
+        "  </tbody>",
+        "</table>",
+        "",
+    ];
+    assert_eq!(render(Format::Html, &["name", "size"], &[&["<b>&co</b>", "1"]]), expected.join("\n"));
+}
+
+#[test]
+fn test_atomic_file() {
+    let path = std::env::temp_dir().join(format!("csvpeek-atomic-{}.csv", std::process::id()));
+    let path_str = path.to_str().unwrap();
+    fs::write(&path, "old").unwrap();
+
+    // Dropped without a commit, the previous file stays
+    let (atomic_file, mut file) = AtomicFile::create(path_str).unwrap();
+    file.write_all(b"partial").unwrap();
+    let temp_path = atomic_file.temp_path.clone();
+    drop(atomic_file);
+    assert!(!temp_path.exists());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+    let (atomic_file, mut file) = AtomicFile::create(path_str).unwrap();
+    file.write_all(b"new").unwrap();
+    drop(file);
+    atomic_file.commit().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_yaml_output() {
+    assert_eq!(render(Format::Yaml, &["name", "zip code"], &[&["Rome", "00118"], &["no", "a: \"b\""]]),
+               "- name: Rome\n  zip code: \"00118\"\n- name: \"no\"\n  zip code: \"a: \\\"b\\\"\"\n");
+    assert_eq!(render(Format::Yaml, &["name"], &[&[""]]), "- name: \"\"\n");
+    assert_eq!(render(Format::Yaml, &["name"], &[]), "[]\n");
+}
+
+#[test]
+fn test_plain_output() {
+    let options = Options { max_col_width: 6, ..test_options() };
+    let rendered = String::from_utf8(render_bytes(Format::Plain, &options, &["id", "comment"],
+                                                  &[&["1", "looks good"], &["22", "ok\nfine"]])).unwrap();
+    assert_eq!(rendered, "id      comme…\n1       looks…\n22      ok fi…\n");
+}
+
+#[test]
+fn test_vertical_output() {
+    let stars = "*".repeat(27);
+    assert_eq!(render(Format::Vertical, &["id", "name"], &[&["1", "Rome"], &["2", ""]]),
+               format!("{stars} 1. row {stars}\n  id: 1\nname: Rome\n{stars} 2. row {stars}\n  id: 2\nname: \n"));
+}
+
+#[test]
+fn test_null_as() {
+    let options = Options { null_as: Some(String::from("NULL")), ..test_options() };
+    assert_eq!(render_bytes(Format::Csv, &options, &["a", "b"], &[&["", "1"], &[" ", ""]]), b"a,b\nNULL,1\n ,NULL\n");
+    // Empty fields stay nulls in the typed formats
+    assert_eq!(render_bytes(Format::Arrow, &options, &["a"], &[&[""]]),
+               render_bytes(Format::Arrow, &test_options(), &["a"], &[&[""]]));
+}
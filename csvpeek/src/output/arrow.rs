@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema};
+use super::RowWriter;
+use crate::value::{self, ColumnType};
+
+/// Arrow IPC stream, with a single record batch written at finish since the
+/// column types are inferred from all the rows
+pub struct ArrowWriter {
+    pub(super) out: Box<dyn Write>,
+    pub(super) schema: Vec<(String, ColumnType)>,
+    pub(super) headers: Vec<String>,
+    pub(super) rows: Vec<Vec<String>>,
+}
+
+impl RowWriter for ArrowWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let batch = record_batch(&self.headers, &self.rows, &self.schema)?;
+        let mut writer = StreamWriter::try_new(&mut self.out, batch.schema_ref())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Builds an Arrow record batch from the rows. Column types come from the
+/// schema when listed there and are inferred from the values otherwise.
+/// Empty fields are nulls
+pub fn record_batch(headers: &[String], rows: &[Vec<String>],
+                    schema: &[(String, ColumnType)]) -> Result<RecordBatch, Box<dyn Error>> {
+    let column_types = super::column_types(headers, rows, schema)?;
+    let mut fields = Vec::new();
+    let mut columns: Vec<ArrayRef> = Vec::new();
+    for (i, (header, column_type)) in headers.iter().zip(column_types).enumerate() {
+        let values: Vec<&str> = rows.iter().map(|row| super::cell(row, i)).collect();
+        let (data_type, column): (DataType, ArrayRef) = match column_type {
+            ColumnType::Integer => (DataType::Int64, Arc::new(Int64Array::from(
+                typed_values(header, &values, "an int", value::parse_integer)?))),
+            ColumnType::Float => (DataType::Float64, Arc::new(Float64Array::from(
+                typed_values(header, &values, "a float", value::parse_number)?))),
+            ColumnType::Text => (DataType::Utf8, Arc::new(StringArray::from(
+                values.iter().map(|field| Some(*field).filter(|field| !field.is_empty())).collect::<Vec<_>>()))),
+        };
+        fields.push(Field::new(header, data_type, true));
+        columns.push(column);
+    }
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+fn typed_values<T>(header: &str, values: &[&str], type_name: &str,
+                   parse: fn(&str) -> Option<T>) -> Result<Vec<Option<T>>, String> {
+    values.iter().map(|field| super::typed_value(header, field, type_name, parse)).collect()
+}
+
+#[test]
+fn test_record_batch() {
+    let headers = vec![String::from("id"), String::from("price"), String::from("zip")];
+    let rows = vec![
+        vec![String::from("1"), String::from("2.5"), String::from("01234")],
+        vec![String::from(""), String::from("3"), String::from("")],
+    ];
+    let batch = record_batch(&headers, &rows, &[(String::from("zip"), ColumnType::Text)]).unwrap();
+    let data_types: Vec<&DataType> = batch.schema_ref().fields().iter().map(|field| field.data_type()).collect();
+    assert_eq!(data_types, vec![&DataType::Int64, &DataType::Float64, &DataType::Utf8]);
+    assert_eq!(batch.column(0).null_count(), 1);
+    assert_eq!(batch.num_rows(), 2);
+
+    assert!(record_batch(&headers, &rows, &[(String::from("zip"), ColumnType::Integer)]).is_ok());
+    assert!(record_batch(&headers, &rows, &[(String::from("size"), ColumnType::Integer)]).is_err());
+    let text_rows = vec![vec![String::from("a"), String::from("1"), String::from("1")]];
+    assert!(record_batch(&headers, &text_rows, &[(String::from("id"), ColumnType::Integer)]).is_err());
+}
+
+#[test]
+fn test_arrow_output() {
+    use arrow_ipc::reader::StreamReader;
+
+    let bytes = super::render_bytes(super::Format::Arrow, &super::test_options(), &["id", "name"], &[&["1", "a"], &["2", ""]]);
+    let batches: Vec<RecordBatch> = StreamReader::try_new(std::io::Cursor::new(bytes), None).unwrap()
+        .collect::<Result<_, _>>().unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].num_rows(), 2);
+    assert_eq!(batches[0].schema_ref().field(0).data_type(), &DataType::Int64);
+    assert_eq!(batches[0].column(1).null_count(), 1);
+}
@@ -0,0 +1,50 @@
+use std::error::Error;
+use std::io::Write;
+use parquet::arrow::ArrowWriter;
+use super::{arrow, RowWriter};
+use crate::value::ColumnType;
+
+/// Parquet file, written at finish since the column types are inferred
+/// from all the rows
+pub struct ParquetWriter {
+    pub(super) out: Box<dyn Write>,
+    pub(super) schema: Vec<(String, ColumnType)>,
+    pub(super) headers: Vec<String>,
+    pub(super) rows: Vec<Vec<String>>,
+}
+
+impl RowWriter for ParquetWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let batch = arrow::record_batch(&self.headers, &self.rows, &self.schema)?;
+        // ArrowWriter needs a Send writer, so the file is built in memory
+        let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), None)?;
+        writer.write(&batch)?;
+        let bytes = writer.into_inner()?;
+        self.out.write_all(&bytes)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parquet_output() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let bytes = super::render_bytes(super::Format::Parquet, &super::test_options(), &["id", "name"], &[&["1", "a"], &["2", ""]]);
+    let reader = SerializedFileReader::new(bytes::Bytes::from(bytes)).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+    let schema = reader.metadata().file_metadata().schema_descr();
+    assert_eq!(schema.column(0).name(), "id");
+    assert_eq!(schema.column(0).physical_type(), parquet::basic::Type::INT64);
+    assert_eq!(schema.column(1).physical_type(), parquet::basic::Type::BYTE_ARRAY);
+}
@@ -0,0 +1,100 @@
+use std::error::Error;
+use rusqlite::{types::Value, Connection};
+use super::RowWriter;
+use crate::value::{self, ColumnType};
+
+/// Table in a SQLite database, created at finish with the column types
+/// inferred from all the rows, and filled in a single transaction
+pub struct SqliteWriter {
+    pub(super) path: String,
+    pub(super) table: String,
+    pub(super) schema: Vec<(String, ColumnType)>,
+    pub(super) headers: Vec<String>,
+    pub(super) rows: Vec<Vec<String>>,
+}
+
+impl RowWriter for SqliteWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let column_types = super::column_types(&self.headers, &self.rows, &self.schema)?;
+        let columns: Vec<String> = self.headers.iter().zip(&column_types)
+            .map(|(header, column_type)| format!("{} {}", quote_identifier(header), sql_type(*column_type)))
+            .collect();
+        let placeholders = vec!["?"; self.headers.len()].join(", ");
+
+        let mut connection = Connection::open(&self.path)?;
+        let transaction = connection.transaction()?;
+        // An existing table is an error rather than something to append to
+        transaction.execute(&format!("CREATE TABLE {} ({})", quote_identifier(&self.table), columns.join(", ")), [])?;
+        {
+            let mut insert = transaction.prepare(&format!("INSERT INTO {} VALUES ({})", quote_identifier(&self.table), placeholders))?;
+            for row in &self.rows {
+                let values = self.headers.iter().zip(&column_types).enumerate()
+                    .map(|(i, (header, column_type))| sql_value(header, super::cell(row, i), *column_type))
+                    .collect::<Result<Vec<Value>, String>>()?;
+                insert.execute(rusqlite::params_from_iter(values))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Integer => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Text => "TEXT",
+    }
+}
+
+fn sql_value(header: &str, field: &str, column_type: ColumnType) -> Result<Value, String> {
+    let value = match column_type {
+        ColumnType::Integer => super::typed_value(header, field, "an int", value::parse_integer)?.map(Value::Integer),
+        ColumnType::Float => super::typed_value(header, field, "a float", value::parse_number)?.map(Value::Real),
+        ColumnType::Text => Some(String::from(field)).filter(|field| !field.is_empty()).map(Value::Text),
+    };
+    Ok(value.unwrap_or(Value::Null))
+}
+
+/// Double quoted SQL identifier, so any header can be a column name
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[test]
+fn test_sqlite_output() {
+    let path = std::env::temp_dir().join(format!("csvpeek-sqlite-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let options = super::test_options();
+    let mut writer = super::sqlite_writer(path.to_str().unwrap(), "sales", &options);
+    writer.write_header(&[String::from("region"), String::from("amount \"eur\"")]).unwrap();
+    writer.write_row(&[String::from("eu"), String::from("10")]).unwrap();
+    writer.write_row(&[String::from("us"), String::from("")]).unwrap();
+    writer.finish().unwrap();
+
+    let connection = Connection::open(&path).unwrap();
+    let total: i64 = connection.query_row("SELECT SUM(\"amount \"\"eur\"\"\") FROM sales", [], |row| row.get(0)).unwrap();
+    assert_eq!(total, 10);
+    let nulls: i64 = connection.query_row("SELECT COUNT(*) FROM sales WHERE \"amount \"\"eur\"\"\" IS NULL", [], |row| row.get(0)).unwrap();
+    assert_eq!(nulls, 1);
+    let declared_type: String = connection
+        .query_row("SELECT type FROM pragma_table_info('sales') WHERE name = 'region'", [], |row| row.get(0)).unwrap();
+    assert_eq!(declared_type, "TEXT");
+    drop(connection);
+
+    // The table is not silently appended to
+    let mut writer = super::sqlite_writer(path.to_str().unwrap(), "sales", &options);
+    writer.write_header(&[String::from("region")]).unwrap();
+    assert!(writer.finish().is_err());
+    std::fs::remove_file(&path).unwrap();
+}
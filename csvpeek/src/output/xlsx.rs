@@ -0,0 +1,70 @@
+use std::error::Error;
+use std::io::Write;
+use rust_xlsxwriter::{Format, Workbook};
+use super::RowWriter;
+use crate::value::{self, ColumnType};
+
+/// Excel workbook with a single sheet and a bold header row. The int and
+/// float columns are written as numeric cells
+pub struct XlsxWriter {
+    pub(super) out: Box<dyn Write>,
+    pub(super) schema: Vec<(String, ColumnType)>,
+    pub(super) headers: Vec<String>,
+    pub(super) rows: Vec<Vec<String>>,
+}
+
+impl RowWriter for XlsxWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.headers = headers.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let column_types = super::column_types(&self.headers, &self.rows, &self.schema)?;
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        let bold = Format::new().set_bold();
+        for (col, header) in self.headers.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+        }
+        for (row_index, row) in self.rows.iter().enumerate() {
+            let xlsx_row = row_index as u32 + 1;
+            for (col, (header, column_type)) in self.headers.iter().zip(&column_types).enumerate() {
+                let field = super::cell(row, col);
+                match column_type {
+                    // Empty fields are left as blank cells
+                    _ if field.trim().is_empty() => {},
+                    ColumnType::Integer | ColumnType::Float => {
+                        let number = super::typed_value(header, field, "a number", value::parse_number)?.unwrap_or_default();
+                        worksheet.write_number(xlsx_row, col as u16, number)?;
+                    },
+                    ColumnType::Text => {
+                        worksheet.write_string(xlsx_row, col as u16, field)?;
+                    },
+                }
+            }
+        }
+        self.out.write_all(&workbook.save_to_buffer()?)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_xlsx_output() {
+    use calamine::{Data, Reader, Xlsx};
+
+    let bytes = super::render_bytes(super::Format::Xlsx, &super::test_options(),
+                                    &["city", "amount"], &[&["Rome", "10"], &["", "2.5"]]);
+    let mut workbook: Xlsx<_> = calamine::open_workbook_from_rs(std::io::Cursor::new(bytes)).unwrap();
+    let range = workbook.worksheet_range_at(0).unwrap().unwrap();
+    assert_eq!(range.get_value((0, 1)), Some(&Data::String(String::from("amount"))));
+    assert_eq!(range.get_value((1, 0)), Some(&Data::String(String::from("Rome"))));
+    assert_eq!(range.get_value((1, 1)), Some(&Data::Float(10.0)));
+    assert_eq!(range.get_value((2, 1)), Some(&Data::Float(2.5)));
+}
@@ -0,0 +1,852 @@
+use std::collections::{HashMap, HashSet};
+use csv::StringRecord;
+use serde_json::json;
+use crate::agg::HyperLogLog;
+use crate::value::{self, ColumnType};
+
+// Distinct values counted exactly per column, before switching to an estimate
+const EXACT_DISTINCT_LIMIT: usize = 100_000;
+// Example values shown per column
+const EXAMPLES: usize = 3;
+
+/// Headers of the rows of Profile::rows
+pub const PROFILE_HEADERS: [&str; 11] = [
+    "column", "type", "empty", "distinct", "min", "max", "max_chars", "avg_chars", "max_bytes", "avg_bytes", "examples",
+];
+
+/// Distinct values of a column, exact until there are too many to keep
+enum Distinct {
+    Exact(HashSet<String>),
+    Approx(HyperLogLog),
+}
+
+impl Distinct {
+    fn insert(&mut self, field: &str) {
+        match self {
+            Distinct::Exact(values) if values.len() >= EXACT_DISTINCT_LIMIT && !values.contains(field) => {
+                let mut hyper_log_log = HyperLogLog::new();
+                for value in values.iter() {
+                    hyper_log_log.insert(value);
+                }
+                hyper_log_log.insert(field);
+                *self = Distinct::Approx(hyper_log_log);
+            },
+            Distinct::Exact(values) => {
+                if !values.contains(field) {
+                    values.insert(String::from(field));
+                }
+            },
+            Distinct::Approx(hyper_log_log) => hyper_log_log.insert(field),
+        }
+    }
+
+    /// The count, and whether it's estimated
+    fn len(&self) -> (u64, bool) {
+        match self {
+            Distinct::Exact(values) => (values.len() as u64, false),
+            Distinct::Approx(hyper_log_log) => (hyper_log_log.estimate(), true),
+        }
+    }
+
+    /// An estimated count starts with '~'
+    fn count(&self) -> String {
+        match self.len() {
+            (count, false) => count.to_string(),
+            (count, true) => format!("~{}", count),
+        }
+    }
+}
+
+/// Summary of the values of a column, for --info
+struct ColumnProfile {
+    column_type: Option<ColumnType>,
+    empty: usize,
+    distinct: Distinct,
+    // Numeric bounds, used when every value is a number, and text ones
+    min_number: Option<f64>,
+    max_number: Option<f64>,
+    min_text: Option<String>,
+    max_text: Option<String>,
+    // Lengths of every field, empty ones too, in characters and in bytes
+    fields: usize,
+    max_chars: usize,
+    total_chars: usize,
+    max_bytes: usize,
+    total_bytes: usize,
+    examples: Vec<String>,
+}
+
+impl ColumnProfile {
+    fn new() -> Self {
+        Self {
+            column_type: None,
+            empty: 0,
+            distinct: Distinct::Exact(HashSet::new()),
+            min_number: None,
+            max_number: None,
+            min_text: None,
+            max_text: None,
+            fields: 0,
+            max_chars: 0,
+            total_chars: 0,
+            max_bytes: 0,
+            total_bytes: 0,
+            examples: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, field: &str) {
+        let chars = field.chars().count();
+        self.fields += 1;
+        self.max_chars = self.max_chars.max(chars);
+        self.total_chars += chars;
+        self.max_bytes = self.max_bytes.max(field.len());
+        self.total_bytes += field.len();
+        if field.trim().is_empty() {
+            self.empty += 1;
+            return;
+        }
+        self.column_type = ColumnType::widen(self.column_type, field);
+        self.distinct.insert(field);
+        if let Some(number) = value::parse_number(field) {
+            self.min_number = Some(self.min_number.map_or(number, |min| min.min(number)));
+            self.max_number = Some(self.max_number.map_or(number, |max| max.max(number)));
+        }
+        if self.min_text.as_deref().is_none_or(|min| field < min) {
+            self.min_text = Some(String::from(field));
+        }
+        if self.max_text.as_deref().is_none_or(|max| field > max) {
+            self.max_text = Some(String::from(field));
+        }
+        if self.examples.len() < EXAMPLES && !self.examples.iter().any(|example| example == field) {
+            self.examples.push(String::from(field));
+        }
+    }
+
+    /// The fields of PROFILE_HEADERS but the column name. A column without
+    /// values has no type
+    fn row(&self) -> Vec<String> {
+        let (min, max) = match self.column_type {
+            Some(ColumnType::Integer | ColumnType::Float) => (
+                self.min_number.map(|min| min.to_string()).unwrap_or_default(),
+                self.max_number.map(|max| max.to_string()).unwrap_or_default(),
+            ),
+            _ => (self.min_text.clone().unwrap_or_default(), self.max_text.clone().unwrap_or_default()),
+        };
+        vec![
+            String::from(self.column_type.map_or("", |column_type| column_type.name())),
+            self.empty.to_string(),
+            self.distinct.count(),
+            min,
+            max,
+            self.max_chars.to_string(),
+            average(self.total_chars, self.fields),
+            self.max_bytes.to_string(),
+            average(self.total_bytes, self.fields),
+            self.examples.join(", "),
+        ]
+    }
+}
+
+impl ColumnProfile {
+    /// The fields of row as a JSON object, with numbers for the counts and
+    /// the bounds of numeric columns, and null for the missing values
+    fn json(&self, name: &str) -> serde_json::Value {
+        let (min, max) = match self.column_type {
+            Some(ColumnType::Integer) => (json!(self.min_number.map(|min| min as i64)), json!(self.max_number.map(|max| max as i64))),
+            Some(ColumnType::Float) => (json!(self.min_number), json!(self.max_number)),
+            _ => (json!(self.min_text), json!(self.max_text)),
+        };
+        let (distinct, distinct_estimated) = self.distinct.len();
+        let average = |total: usize| (self.fields > 0).then(|| total as f64 / self.fields as f64);
+        json!({
+            "name": name,
+            "type": self.column_type.map(|column_type| column_type.name()),
+            "empty": self.empty,
+            "distinct": distinct,
+            "distinct_estimated": distinct_estimated,
+            "min": min,
+            "max": max,
+            "max_chars": self.max_chars,
+            "avg_chars": average(self.total_chars),
+            "max_bytes": self.max_bytes,
+            "avg_bytes": average(self.total_bytes),
+            "examples": self.examples,
+        })
+    }
+}
+
+/// Average length with a decimal, empty without fields
+fn average(total: usize, fields: usize) -> String {
+    match fields {
+        0 => String::new(),
+        _ => format!("{:.1}", total as f64 / fields as f64),
+    }
+}
+
+/// Profile of every column of the rows, computed in one pass. Missing fields
+/// count as empty
+pub struct Profile {
+    columns: Vec<ColumnProfile>,
+}
+
+impl Profile {
+    pub fn new(columns: usize) -> Self {
+        Self { columns: (0..columns).map(|_| ColumnProfile::new()).collect() }
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        for (i, column) in self.columns.iter_mut().enumerate() {
+            column.update(record.get(i).unwrap_or_default());
+        }
+    }
+
+    /// A row per column with its name and PROFILE_HEADERS
+    pub fn rows(&self, headers: &StringRecord) -> Vec<Vec<String>> {
+        headers.iter().zip(&self.columns)
+            .map(|(name, column)| std::iter::once(String::from(name)).chain(column.row()).collect())
+            .collect()
+    }
+
+    /// An object per column with its name and PROFILE_HEADERS, for --format json
+    pub fn json(&self, headers: &StringRecord) -> Vec<serde_json::Value> {
+        headers.iter().zip(&self.columns).map(|(name, column)| column.json(name)).collect()
+    }
+}
+
+/// Headers of the rows of Describe::rows
+pub const DESCRIBE_HEADERS: [&str; 10] = ["column", "count", "distinct", "mean", "std", "min", "25%", "50%", "75%", "max"];
+
+// Values kept per column for exact quartiles, before switching to estimates
+const EXACT_QUANTILE_LIMIT: usize = 100_000;
+
+/// Streaming estimate of a quantile with the P² algorithm, which follows
+/// the quantile and its neighbours with 5 markers instead of keeping the
+/// values. It starts from the values seen so far, sorted
+struct P2Quantile {
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    fn new(quantile: f64, sorted: &[f64]) -> Self {
+        let increments = [0.0, quantile / 2.0, quantile, (1.0 + quantile) / 2.0, 1.0];
+        let last = (sorted.len() - 1) as f64;
+        let desired = increments.map(|increment| 1.0 + last * increment);
+        let positions = desired.map(f64::round);
+        let heights = positions.map(|position| sorted[position as usize - 1]);
+        Self { heights, positions, desired, increments }
+    }
+
+    fn update(&mut self, value: f64) {
+        let (heights, positions) = (&mut self.heights, &mut self.positions);
+        let cell = if value < heights[0] {
+            heights[0] = value;
+            0
+        } else if value >= heights[4] {
+            heights[4] = value;
+            3
+        } else {
+            (0..4).rfind(|&i| heights[i] <= value).unwrap_or_default()
+        };
+        for position in &mut positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+        // The middle markers move a position towards where they should be,
+        // with a parabolic prediction of their height, or a linear one when
+        // it leaves them out of order
+        for i in 1..4 {
+            let offset = self.desired[i] - positions[i];
+            if (offset >= 1.0 && positions[i + 1] - positions[i] > 1.0) || (offset <= -1.0 && positions[i - 1] - positions[i] < -1.0) {
+                let step = offset.signum();
+                let parabolic = heights[i] + step / (positions[i + 1] - positions[i - 1])
+                    * ((positions[i] - positions[i - 1] + step) * (heights[i + 1] - heights[i]) / (positions[i + 1] - positions[i])
+                        + (positions[i + 1] - positions[i] - step) * (heights[i] - heights[i - 1]) / (positions[i] - positions[i - 1]));
+                heights[i] = match heights[i - 1] < parabolic && parabolic < heights[i + 1] {
+                    true => parabolic,
+                    false => {
+                        let j = if step > 0.0 { i + 1 } else { i - 1 };
+                        heights[i] + step * (heights[j] - heights[i]) / (positions[j] - positions[i])
+                    },
+                };
+                positions[i] += step;
+            }
+        }
+    }
+
+    fn estimate(&self) -> f64 {
+        self.heights[2]
+    }
+}
+
+/// Quartiles of a column, exact until there are too many values to keep
+enum Quartiles {
+    Exact(Vec<f64>),
+    Approx(Box<[P2Quantile; 3]>),
+}
+
+/// Quantile of sorted values, interpolating between the two closest ones
+fn quantile(sorted: &[f64], quantile: f64) -> f64 {
+    let position = (sorted.len() - 1) as f64 * quantile;
+    let (below, above) = (sorted[position.floor() as usize], sorted[position.ceil() as usize]);
+    below + (above - below) * position.fract()
+}
+
+/// Summary statistics of a column, for --stats
+struct ColumnStats {
+    count: usize,
+    distinct: Distinct,
+    numeric: bool,
+    // Welford's online algorithm, like the deviations of --agg
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    quartiles: Quartiles,
+}
+
+impl ColumnStats {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            distinct: Distinct::Exact(HashSet::new()),
+            numeric: true,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            quartiles: Quartiles::Exact(Vec::new()),
+        }
+    }
+
+    fn update(&mut self, field: &str) {
+        if field.trim().is_empty() {
+            return;
+        }
+        self.count += 1;
+        self.distinct.insert(field);
+        if !self.numeric {
+            return;
+        }
+        let Some(number) = value::parse_number(field) else {
+            // A text value makes it a text column, so the numbers are dropped
+            self.numeric = false;
+            self.quartiles = Quartiles::Exact(Vec::new());
+            return;
+        };
+        let delta = number - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (number - self.mean);
+        self.min = self.min.min(number);
+        self.max = self.max.max(number);
+        match self.quartiles {
+            Quartiles::Exact(ref mut values) if values.len() >= EXACT_QUANTILE_LIMIT => {
+                values.sort_by(f64::total_cmp);
+                let mut estimates = [0.25, 0.5, 0.75].map(|q| P2Quantile::new(q, values));
+                for estimate in &mut estimates {
+                    estimate.update(number);
+                }
+                self.quartiles = Quartiles::Approx(Box::new(estimates));
+            },
+            Quartiles::Exact(ref mut values) => values.push(number),
+            Quartiles::Approx(ref mut estimates) => {
+                for estimate in estimates.iter_mut() {
+                    estimate.update(number);
+                }
+            },
+        }
+    }
+
+    /// The fields of DESCRIBE_HEADERS but the column name. Text columns only
+    /// have the count and distinct values
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![self.count.to_string(), self.distinct.count()];
+        if !self.numeric || self.count == 0 {
+            row.resize(DESCRIBE_HEADERS.len() - 1, String::new());
+            return row;
+        }
+        let quartiles = match self.quartiles {
+            Quartiles::Exact(ref values) => {
+                let mut sorted = values.clone();
+                sorted.sort_by(f64::total_cmp);
+                [0.25, 0.5, 0.75].map(|q| quantile(&sorted, q))
+            },
+            Quartiles::Approx(ref estimates) => [0, 1, 2].map(|i| estimates[i].estimate()),
+        };
+        let std = match self.count {
+            1 => String::new(),
+            count => (self.m2 / (count - 1) as f64).sqrt().to_string(),
+        };
+        row.extend([self.mean.to_string(), std, self.min.to_string()]);
+        row.extend(quartiles.iter().map(f64::to_string));
+        row.push(self.max.to_string());
+        row
+    }
+}
+
+/// Statistics of the columns of the rows, like describe() of pandas,
+/// computed in one pass
+pub struct Describe {
+    columns: Vec<(usize, ColumnStats)>,
+}
+
+impl Describe {
+    pub fn new(columns: &[usize]) -> Self {
+        Self { columns: columns.iter().map(|&column| (column, ColumnStats::new())).collect() }
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        for (column, stats) in &mut self.columns {
+            stats.update(record.get(*column).unwrap_or_default());
+        }
+    }
+
+    /// A row per column with its name and DESCRIBE_HEADERS
+    pub fn rows(&self, headers: &StringRecord) -> Vec<Vec<String>> {
+        self.columns.iter()
+            .map(|(column, stats)| std::iter::once(String::from(&headers[*column])).chain(stats.row()).collect())
+            .collect()
+    }
+}
+
+/// Occurrences of each value of a column, for --freq
+pub struct Frequency {
+    column: usize,
+    rows: usize,
+    // Count and first position of each value, so ties keep that order
+    counts: HashMap<String, (usize, usize)>,
+}
+
+impl Frequency {
+    pub fn new(column: usize) -> Self {
+        Self { column, rows: 0, counts: HashMap::new() }
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        let field = record.get(self.column).unwrap_or_default();
+        self.rows += 1;
+        match self.counts.get_mut(field) {
+            Some((count, _)) => *count += 1,
+            None => {
+                let position = self.counts.len();
+                self.counts.insert(String::from(field), (1, position));
+            },
+        }
+    }
+
+    /// The column name followed by "count" and "percent"
+    pub fn headers(&self, headers: &StringRecord) -> Vec<String> {
+        vec![String::from(&headers[self.column]), String::from("count"), String::from("percent")]
+    }
+
+    /// The values with their count and percentage of the rows, the most
+    /// common first, only the top ones when given
+    pub fn rows(&self, top: Option<usize>) -> Vec<Vec<String>> {
+        let mut counts: Vec<(&String, &(usize, usize))> = self.counts.iter().collect();
+        counts.sort_by(|(_, (count_a, position_a)), (_, (count_b, position_b))| {
+            count_b.cmp(count_a).then(position_a.cmp(position_b))
+        });
+        counts.into_iter()
+            .take(top.unwrap_or(usize::MAX))
+            .map(|(value, &(count, _))| {
+                vec![value.clone(), count.to_string(), format!("{:.1}", count as f64 * 100.0 / self.rows as f64)]
+            })
+            .collect()
+    }
+}
+
+/// Sums of a pair of columns to compute their Pearson correlation in one
+/// pass, with the co-moment version of Welford's algorithm. Only the rows
+/// with a number in both columns count
+#[derive(Default)]
+struct CoMoments {
+    count: usize,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    c_xy: f64,
+}
+
+impl CoMoments {
+    fn update(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        let delta_x = x - self.mean_x;
+        self.mean_x += delta_x / self.count as f64;
+        let delta_y = y - self.mean_y;
+        self.mean_y += delta_y / self.count as f64;
+        self.m2_x += delta_x * (x - self.mean_x);
+        self.m2_y += delta_y * (y - self.mean_y);
+        self.c_xy += delta_x * (y - self.mean_y);
+    }
+
+    /// None with less than two rows or a constant column
+    fn correlation(&self) -> Option<f64> {
+        match self.count >= 2 && self.m2_x > 0.0 && self.m2_y > 0.0 {
+            true => Some(self.c_xy / (self.m2_x * self.m2_y).sqrt()),
+            false => None,
+        }
+    }
+}
+
+/// Pearson correlations between every pair of columns, for --corr. Without
+/// columns given, the ones whose values are all numbers are kept at the end
+pub struct Correlation {
+    columns: Vec<usize>,
+    only_numeric: bool,
+    numeric: Vec<bool>,
+    // Pairs i < j of the columns, row by row
+    pairs: Vec<CoMoments>,
+    numbers: Vec<Option<f64>>,
+}
+
+impl Correlation {
+    pub fn new(columns: Vec<usize>, only_numeric: bool) -> Self {
+        let pairs = (0..columns.len() * columns.len().saturating_sub(1) / 2).map(|_| CoMoments::default()).collect();
+        Self { numeric: vec![true; columns.len()], numbers: vec![None; columns.len()], columns, only_numeric, pairs }
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        for (i, &column) in self.columns.iter().enumerate() {
+            let field = record.get(column).unwrap_or_default();
+            self.numbers[i] = value::parse_number(field);
+            if self.numbers[i].is_none() && !field.trim().is_empty() {
+                self.numeric[i] = false;
+            }
+        }
+        let mut pairs = self.pairs.iter_mut();
+        for (i, x) in self.numbers.iter().enumerate() {
+            for y in &self.numbers[i + 1..] {
+                let pair = pairs.next().expect("a pair for each two columns");
+                if let (Some(x), Some(y)) = (x, y) {
+                    pair.update(*x, *y);
+                }
+            }
+        }
+    }
+
+    fn kept(&self) -> Vec<usize> {
+        (0..self.columns.len()).filter(|&i| !self.only_numeric || self.numeric[i]).collect()
+    }
+
+    /// "column" followed by the names of the columns
+    pub fn headers(&self, headers: &StringRecord) -> Vec<String> {
+        std::iter::once(String::from("column"))
+            .chain(self.kept().iter().map(|&i| String::from(&headers[self.columns[i]])))
+            .collect()
+    }
+
+    /// The matrix of correlations, with 4 decimals, empty when they can't be
+    /// computed. The columns are only known after the last row
+    pub fn rows(&self, headers: &StringRecord) -> Vec<Vec<String>> {
+        let n = self.columns.len();
+        // Position of the pair i < j in the pairs
+        let pair = |i: usize, j: usize| i * n - i * (i + 1) / 2 + (j - i - 1);
+        let kept = self.kept();
+        kept.iter()
+            .map(|&i| std::iter::once(String::from(&headers[self.columns[i]]))
+                .chain(kept.iter().map(|&j| {
+                    let correlation = match i.cmp(&j) {
+                        std::cmp::Ordering::Equal => Some(1.0),
+                        std::cmp::Ordering::Less => self.pairs[pair(i, j)].correlation(),
+                        std::cmp::Ordering::Greater => self.pairs[pair(j, i)].correlation(),
+                    };
+                    correlation.map(|correlation| format!("{:.4}", correlation)).unwrap_or_default()
+                }))
+                .collect())
+            .collect()
+    }
+}
+
+/// How --outliers finds the values out of the usual range
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OutlierMethod {
+    /// More standard deviations from the mean than the threshold, 3 by default
+    Zscore,
+    /// Further below the first quartile or above the third one than the threshold
+    /// times the interquartile range, 1.5 by default
+    Iqr,
+}
+
+/// Rows with an outlier value in a column, for --outliers. The rows with a
+/// number are kept until the end, since the range needs all of them
+pub struct Outliers {
+    column: usize,
+    method: OutlierMethod,
+    threshold: f64,
+    rows: Vec<(usize, f64, StringRecord)>,
+}
+
+impl Outliers {
+    pub fn new(column: usize, method: OutlierMethod, threshold: Option<f64>) -> Self {
+        let threshold = threshold.unwrap_or(match method {
+            OutlierMethod::Zscore => 3.0,
+            OutlierMethod::Iqr => 1.5,
+        });
+        Self { column, method, threshold, rows: Vec::new() }
+    }
+
+    /// Fields that aren't numbers are ignored
+    pub fn update(&mut self, row_index: usize, record: StringRecord) {
+        if let Some(number) = value::parse_number(record.get(self.column).unwrap_or_default()) {
+            self.rows.push((row_index, number, record));
+        }
+    }
+
+    /// The rows with an outlier, in the order of the input
+    pub fn rows(self) -> Vec<(usize, StringRecord)> {
+        let values: Vec<f64> = self.rows.iter().map(|&(_, value, _)| value).collect();
+        if values.len() < 2 {
+            return Vec::new();
+        }
+        let (low, high) = match self.method {
+            OutlierMethod::Zscore => {
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+                (mean - self.threshold * variance.sqrt(), mean + self.threshold * variance.sqrt())
+            },
+            OutlierMethod::Iqr => {
+                let mut sorted = values;
+                sorted.sort_by(f64::total_cmp);
+                let (first, third) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+                (first - self.threshold * (third - first), third + self.threshold * (third - first))
+            },
+        };
+        self.rows.into_iter()
+            .filter(|&(_, value, _)| value < low || value > high)
+            .map(|(row_index, _, record)| (row_index, record))
+            .collect()
+    }
+}
+
+/// Width of the longest bar of --hist
+pub const BAR_WIDTH: usize = 40;
+// Eighths of a block, for the ends of the bars of --hist
+const BAR_BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+/// Distribution of the numbers of a column, for --hist. The values are kept
+/// until the end, since the bins depend on the min and max
+pub struct Histogram {
+    column: usize,
+    values: Vec<f64>,
+    skipped: usize,
+}
+
+impl Histogram {
+    pub fn new(column: usize) -> Self {
+        Self { column, values: Vec::new(), skipped: 0 }
+    }
+
+    /// Empty fields are ignored, and other fields that aren't numbers skipped
+    pub fn update(&mut self, record: &StringRecord) {
+        let field = record.get(self.column).unwrap_or_default();
+        match value::parse_number(field) {
+            Some(number) => self.values.push(number),
+            None if field.trim().is_empty() => {},
+            None => self.skipped += 1,
+        }
+    }
+
+    /// Values that weren't numbers
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// A line per bin of the same width between the min and max, like
+    /// "1 - 2 | ████▌ 9", with the longest bar of the given width
+    pub fn lines(&self, bins: usize, width: usize) -> Vec<String> {
+        let (Some(min), Some(max)) = (self.values.iter().copied().reduce(f64::min), self.values.iter().copied().reduce(f64::max)) else {
+            return Vec::new();
+        };
+        // A single value gets a single bin
+        let bins = if min == max { 1 } else { bins };
+        let bin_width = (max - min) / bins as f64;
+        let mut counts = vec![0usize; bins];
+        for value in &self.values {
+            let bin = match bin_width {
+                0.0 => 0,
+                _ => (((value - min) / bin_width) as usize).min(bins - 1),
+            };
+            counts[bin] += 1;
+        }
+        // Decimals only when the edges need them
+        let decimals = match [min, bin_width].iter().all(|edge| edge.fract() == 0.0) {
+            true => 0,
+            false => 2,
+        };
+        let edges: Vec<(String, String)> = (0..bins)
+            .map(|bin| {
+                let start = min + bin_width * bin as f64;
+                (format!("{:.*}", decimals, start), format!("{:.*}", decimals, if bin == bins - 1 { max } else { start + bin_width }))
+            })
+            .collect();
+        let start_width = edges.iter().map(|(start, _)| start.len()).max().unwrap_or_default();
+        let end_width = edges.iter().map(|(_, end)| end.len()).max().unwrap_or_default();
+        let max_count = counts.iter().copied().max().unwrap_or_default();
+        edges.iter().zip(&counts)
+            .map(|((start, end), &count)| {
+                let bar = format!("{} {}", bar(count, max_count, width), count);
+                format!("{:>start_width$} - {:>end_width$} | {}", start, end, bar.trim_start())
+            })
+            .collect()
+    }
+}
+
+/// A bar of blocks as long as count is of max_count, in eighths of a block
+fn bar(count: usize, max_count: usize, width: usize) -> String {
+    let eighths = match max_count {
+        0 => 0,
+        _ => count * width * 8 / max_count,
+    };
+    let mut bar = BAR_BLOCKS[7].to_string().repeat(eighths / 8);
+    if eighths % 8 > 0 {
+        bar.push(BAR_BLOCKS[eighths % 8 - 1]);
+    }
+    bar
+}
+
+#[test]
+fn test_profile() {
+    let headers = StringRecord::from(vec!["id", "city", "price"]);
+    let mut profile = Profile::new(headers.len());
+    for row in [vec!["3", "Zürich", "1.5"], vec!["10", "", "2"], vec!["2", "Lima", ""], vec!["3", "Oslo"]] {
+        profile.update(&StringRecord::from(row));
+    }
+    assert_eq!(profile.rows(&headers), vec![
+        vec!["id", "int", "0", "3", "2", "10", "2", "1.2", "2", "1.2", "3, 10, 2"],
+        vec!["city", "text", "1", "3", "Lima", "Zürich", "6", "3.5", "7", "3.8", "Zürich, Lima, Oslo"],
+        vec!["price", "float", "2", "2", "1.5", "2", "3", "1.0", "3", "1.0", "1.5, 2"],
+    ]);
+
+    let mut profile = Profile::new(1);
+    profile.update(&StringRecord::from(vec![""]));
+    assert_eq!(profile.rows(&StringRecord::from(vec!["notes"])), vec![vec!["notes", "", "1", "0", "", "", "0", "0.0", "0", "0.0", ""]]);
+}
+
+#[test]
+fn test_profile_json() {
+    let headers = StringRecord::from(vec!["id", "city", "price"]);
+    let mut profile = Profile::new(headers.len());
+    for row in [["3", "Paris", "1.5"], ["10", "", "2"]] {
+        profile.update(&StringRecord::from(row.to_vec()));
+    }
+    let columns = profile.json(&headers);
+    assert_eq!(columns[0], json!({
+        "name": "id", "type": "int", "empty": 0, "distinct": 2, "distinct_estimated": false, "min": 3, "max": 10,
+        "max_chars": 2, "avg_chars": 1.5, "max_bytes": 2, "avg_bytes": 1.5, "examples": ["3", "10"],
+    }));
+    assert_eq!((&columns[1]["min"], &columns[1]["empty"]), (&json!("Paris"), &json!(1)));
+    assert_eq!(columns[2]["min"], json!(1.5));
+    assert_eq!(Profile::new(1).json(&StringRecord::from(vec!["notes"]))[0]["avg_chars"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_profile_approx_distinct() {
+    let mut column = ColumnProfile::new();
+    for i in 0..EXACT_DISTINCT_LIMIT + 1000 {
+        column.update(&i.to_string());
+    }
+    let distinct = column.row()[2].clone();
+    let estimate: f64 = distinct.strip_prefix('~').unwrap().parse().unwrap();
+    assert!((estimate / (EXACT_DISTINCT_LIMIT + 1000) as f64 - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn test_describe() {
+    let headers = StringRecord::from(vec!["id", "city", "price"]);
+    let mut describe = Describe::new(&[0, 1, 2]);
+    for row in [["1", "Paris", "1"], ["2", "Lima", "x"], ["3", "Paris", ""], ["4", "", ""]] {
+        describe.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(describe.rows(&headers), vec![
+        vec!["id", "4", "4", "2.5", "1.2909944487358056", "1", "1.75", "2.5", "3.25", "4"],
+        vec!["city", "3", "2", "", "", "", "", "", "", ""],
+        vec!["price", "2", "2", "", "", "", "", "", "", ""],
+    ]);
+    assert_eq!(Describe::new(&[2]).rows(&headers), vec![vec!["price", "0", "0", "", "", "", "", "", "", ""]]);
+}
+
+#[test]
+fn test_p2_quantile() {
+    // A shuffled 0..20000, switching to estimates after the first 1000
+    let values: Vec<f64> = (0..20000).map(|i| ((i * 7919) % 20000) as f64).collect();
+    let mut sorted = values[..1000].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mut median = P2Quantile::new(0.5, &sorted);
+    for &value in &values[1000..] {
+        median.update(value);
+    }
+    assert!((median.estimate() - 10000.0).abs() < 200.0, "{}", median.estimate());
+    assert_eq!(quantile(&[1.0, 2.0, 3.0, 4.0], 0.25), 1.75);
+}
+
+#[test]
+fn test_frequency() {
+    let headers = StringRecord::from(vec!["id", "city"]);
+    let mut frequency = Frequency::new(1);
+    for city in ["Lima", "Paris", "", "Paris", "Oslo", "Lima", "Paris"] {
+        frequency.update(&StringRecord::from(vec!["1", city]));
+    }
+    assert_eq!(frequency.headers(&headers), vec!["city", "count", "percent"]);
+    assert_eq!(frequency.rows(None), vec![
+        vec!["Paris", "3", "42.9"], vec!["Lima", "2", "28.6"], vec!["", "1", "14.3"], vec!["Oslo", "1", "14.3"],
+    ]);
+    assert_eq!(frequency.rows(Some(2)).len(), 2);
+}
+
+#[test]
+fn test_histogram() {
+    let mut histogram = Histogram::new(0);
+    for value in ["1", "2", "2", "3", "9", "", "n/a"] {
+        histogram.update(&StringRecord::from(vec![value]));
+    }
+    assert_eq!(histogram.skipped(), 1);
+    assert_eq!(histogram.lines(4, 8), vec!["1 - 3 | ████████ 3", "3 - 5 | ██▋ 1", "5 - 7 | 0", "7 - 9 | ██▋ 1"]);
+    let mut histogram = Histogram::new(0);
+    for value in ["0.5", "1", "10"] {
+        histogram.update(&StringRecord::from(vec![value]));
+    }
+    assert_eq!(histogram.lines(2, 4)[1], "5.25 - 10.00 | ██ 1");
+    assert_eq!(bar(1, 3, 4), "█▎");
+    assert!(Histogram::new(0).lines(10, 40).is_empty());
+}
+
+#[test]
+fn test_correlation() {
+    let headers = StringRecord::from(vec!["x", "name", "y", "z"]);
+    let mut correlation = Correlation::new(vec![0, 1, 2, 3], true);
+    for row in [["1", "a", "2", "5"], ["2", "b", "4", "5"], ["3", "c", "6.5", "5"], ["4", "d", "", "5"], ["5", "", "1", "5"]] {
+        correlation.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(correlation.headers(&headers), vec!["column", "x", "y", "z"]);
+    assert_eq!(correlation.rows(&headers), vec![
+        vec!["x", "1.0000", "-0.2110", ""], vec!["y", "-0.2110", "1.0000", ""], vec!["z", "", "", "1.0000"],
+    ]);
+
+    let mut correlation = Correlation::new(vec![2, 0], false);
+    for row in [["1", "a", "2", "5"], ["2", "b", "4", "5"]] {
+        correlation.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(correlation.rows(&headers), vec![vec!["y", "1.0000", "1.0000"], vec!["x", "1.0000", "1.0000"]]);
+}
+
+#[test]
+fn test_outliers() {
+    let outliers = |method: OutlierMethod, threshold: Option<f64>| {
+        let mut outliers = Outliers::new(1, method, threshold);
+        let values = ["10", "11", "9", "10", "12", "", "10", "95", "11", "-40", "9", "10"];
+        for (row_index, value) in values.iter().enumerate() {
+            outliers.update(row_index, StringRecord::from(vec!["s1", value]));
+        }
+        outliers.rows().into_iter().map(|(row_index, _)| row_index).collect::<Vec<usize>>()
+    };
+    assert_eq!(outliers(OutlierMethod::Iqr, None), vec![7, 9]);
+    assert_eq!(outliers(OutlierMethod::Zscore, None), Vec::<usize>::new());
+    assert_eq!(outliers(OutlierMethod::Zscore, Some(2.0)), vec![7]);
+    assert!(Outliers::new(0, OutlierMethod::Zscore, None).rows().is_empty());
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use csv::StringRecord;
+use crate::agg::{Aggregator, Function};
+use crate::columns::{column_index, with_field};
+use crate::input::IndexedRecord;
+
+/// Replaces each row with the rows a reshaping makes of it, which keep the
+/// index of the row in the input
+pub fn expand<'a, I, F>(rows: I, reshape: F) -> impl Iterator<Item = IndexedRecord> + 'a
+where I: Iterator<Item = IndexedRecord> + 'a, F: Fn(&StringRecord) -> Vec<StringRecord> + 'a {
+    rows.flat_map(move |row| -> Vec<IndexedRecord> {
+        match row {
+            Ok((row_index, record)) => reshape(&record).into_iter().map(|record| Ok((row_index, record))).collect(),
+            Err(err) => vec![Err(err)],
+        }
+    })
+}
+
+/// Long to wide table of --pivot: a row per value of the index column, and
+/// a column per value of the columns column, with the aggregate of the
+/// values of their rows. Rows and columns are kept in the order they first
+/// appear, and cells without rows are empty
+pub struct Pivot {
+    index: usize,
+    columns: usize,
+    values: usize,
+    function: Function,
+    row_indices: HashMap<String, usize>,
+    row_keys: Vec<String>,
+    column_indices: HashMap<String, usize>,
+    column_keys: Vec<String>,
+    cells: Vec<HashMap<usize, Aggregator>>,
+}
+
+impl Pivot {
+    /// Parses "index=date, columns=region, values=sales, agg=sum". The
+    /// aggregate is a sum when not given
+    pub fn parse(pivot_str: &str, headers: &StringRecord) -> Result<Self, String> {
+        let (mut index, mut columns, mut values, mut function) = (None, None, None, Function::Sum);
+        for pair in pivot_str.split(',') {
+            let (key, value) = pair.split_once('=')
+                .ok_or_else(|| format!("Expected key=value in --pivot, got '{}'", pair))?;
+            match key.trim() {
+                "index" => index = Some(column_index(headers, value, "--pivot")?),
+                "columns" => columns = Some(column_index(headers, value, "--pivot")?),
+                "values" => values = Some(column_index(headers, value, "--pivot")?),
+                "agg" => function = Function::parse(value)?,
+                key => return Err(format!("Unknown key '{}' in --pivot, expected one of: index, columns, values, agg", key)),
+            }
+        }
+        let missing = |key: &str| format!("Missing {}= in --pivot", key);
+        Ok(Self {
+            index: index.ok_or_else(|| missing("index"))?,
+            columns: columns.ok_or_else(|| missing("columns"))?,
+            values: values.ok_or_else(|| missing("values"))?,
+            function,
+            row_indices: HashMap::new(),
+            row_keys: Vec::new(),
+            column_indices: HashMap::new(),
+            column_keys: Vec::new(),
+            cells: Vec::new(),
+        })
+    }
+
+    pub fn update(&mut self, record: &StringRecord) {
+        let row_key = record.get(self.index).unwrap_or_default();
+        let row = match self.row_indices.get(row_key) {
+            Some(&row) => row,
+            None => {
+                self.row_indices.insert(String::from(row_key), self.row_keys.len());
+                self.row_keys.push(String::from(row_key));
+                self.cells.push(HashMap::new());
+                self.row_keys.len() - 1
+            },
+        };
+        let column_key = record.get(self.columns).unwrap_or_default();
+        let column = match self.column_indices.get(column_key) {
+            Some(&column) => column,
+            None => {
+                self.column_indices.insert(String::from(column_key), self.column_keys.len());
+                self.column_keys.push(String::from(column_key));
+                self.column_keys.len() - 1
+            },
+        };
+        let function = self.function;
+        self.cells[row].entry(column).or_insert_with(|| Aggregator::new(function))
+            .update(record.get(self.values).unwrap_or_default());
+    }
+
+    /// The index header followed by the values of the columns column, so
+    /// they are only known after the last row
+    pub fn headers(&self, headers: &StringRecord) -> Vec<String> {
+        std::iter::once(String::from(&headers[self.index]))
+            .chain(self.column_keys.iter().cloned())
+            .collect()
+    }
+
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        self.row_keys.iter().zip(&self.cells)
+            .map(|(row_key, cells)| std::iter::once(row_key.clone())
+                .chain((0..self.column_keys.len()).map(|column| cells.get(&column).map(Aggregator::result).unwrap_or_default()))
+                .collect())
+            .collect()
+    }
+}
+
+/// Wide to long rows of --melt: each row becomes a row per value column,
+/// with the id columns followed by the name and the value of that column
+pub struct Melt {
+    id_columns: Vec<usize>,
+    value_columns: Vec<usize>,
+    value_names: Vec<String>,
+}
+
+impl Melt {
+    /// Parses "id_cols=date, value_cols=us,eu,apac". The names after a key
+    /// are its columns, and the value columns are all the other ones when
+    /// not given
+    pub fn parse(melt_str: &str, headers: &StringRecord) -> Result<Self, String> {
+        let (mut id_columns, mut value_columns) = (Vec::new(), None);
+        let mut key = None;
+        for part in melt_str.split(',') {
+            let name = match part.split_once('=') {
+                Some((new_key, name)) => {
+                    key = Some(new_key.trim());
+                    name
+                },
+                None => part,
+            };
+            let column = column_index(headers, name, "--melt")?;
+            match key {
+                Some("id_cols") => id_columns.push(column),
+                Some("value_cols") => value_columns.get_or_insert_with(Vec::new).push(column),
+                Some(key) => return Err(format!("Unknown key '{}' in --melt, expected id_cols or value_cols", key)),
+                None => return Err(format!("Expected id_cols=... in --melt, got '{}'", melt_str)),
+            }
+        }
+        let value_columns = value_columns
+            .unwrap_or_else(|| (0..headers.len()).filter(|column| !id_columns.contains(column)).collect());
+        if value_columns.is_empty() {
+            return Err(String::from("No value columns left to melt in --melt"));
+        }
+        let value_names = value_columns.iter().map(|&column| String::from(&headers[column])).collect();
+        Ok(Self { id_columns, value_columns, value_names })
+    }
+
+    /// The id headers followed by "variable" and "value"
+    pub fn headers(&self, headers: &StringRecord) -> StringRecord {
+        self.id_columns.iter().map(|&column| &headers[column])
+            .chain(["variable", "value"])
+            .collect()
+    }
+
+    pub fn rows(&self, record: &StringRecord) -> Vec<StringRecord> {
+        self.value_columns.iter().zip(&self.value_names)
+            .map(|(&column, name)| self.id_columns.iter().map(|&id_column| record.get(id_column).unwrap_or_default())
+                .chain([name.as_str(), record.get(column).unwrap_or_default()])
+                .collect())
+            .collect()
+    }
+}
+
+/// Rows of --explode: a copy of the row for each value packed in a field
+pub struct Explode {
+    column: usize,
+    delimiter: String,
+}
+
+impl Explode {
+    /// Parses "tags:|", a column and the delimiter of its values
+    pub fn parse(explode_str: &str, headers: &StringRecord) -> Result<Self, String> {
+        match explode_str.split_once(':') {
+            Some((name, delimiter)) if !delimiter.is_empty() => {
+                Ok(Self { column: column_index(headers, name, "--explode")?, delimiter: String::from(delimiter) })
+            },
+            _ => Err(format!("Expected column:delimiter in --explode, got '{}'", explode_str)),
+        }
+    }
+
+    /// The values are trimmed, and an empty field still makes a row
+    pub fn rows(&self, record: &StringRecord) -> Vec<StringRecord> {
+        let Some(field) = record.get(self.column) else {
+            return vec![record.clone()];
+        };
+        field.split(self.delimiter.as_str())
+            .map(|value| with_field(record, self.column, value.trim()))
+            .collect()
+    }
+}
+
+#[test]
+fn test_pivot() {
+    let headers = StringRecord::from(vec!["date", "region", "sales"]);
+    let mut pivot = Pivot::parse("index=date, columns=region, values=sales", &headers).unwrap();
+    for row in [["mon", "north", "10"], ["mon", "south", "5"], ["tue", "south", "7"], ["mon", "north", "1.5"]] {
+        pivot.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(pivot.headers(&headers), vec!["date", "north", "south"]);
+    assert_eq!(pivot.rows(), vec![vec!["mon", "11.5", "5"], vec!["tue", "", "7"]]);
+
+    let mut pivot = Pivot::parse("index=region,columns=date,values=sales,agg=count", &headers).unwrap();
+    for row in [["mon", "north", "10"], ["mon", "north", "1"]] {
+        pivot.update(&StringRecord::from(row.to_vec()));
+    }
+    assert_eq!(pivot.rows(), vec![vec!["north", "2"]]);
+
+    assert!(Pivot::parse("index=date,columns=region", &headers).is_err());
+    assert!(Pivot::parse("index=date,columns=region,values=qty", &headers).is_err());
+    assert!(Pivot::parse("index=date,columns=region,values=sales,agg=median", &headers).is_err());
+    assert!(Pivot::parse("index=date,rows=region,values=sales", &headers).is_err());
+}
+
+#[test]
+fn test_melt() {
+    let headers = StringRecord::from(vec!["date", "us", "eu", "apac"]);
+    let melt = Melt::parse("id_cols=date, value_cols=us,eu", &headers).unwrap();
+    assert_eq!(melt.headers(&headers), vec!["date", "variable", "value"]);
+    assert_eq!(melt.rows(&StringRecord::from(vec!["mon", "1", "2", "3"])),
+               vec![StringRecord::from(vec!["mon", "us", "1"]), StringRecord::from(vec!["mon", "eu", "2"])]);
+
+    let melt = Melt::parse("id_cols=date", &headers).unwrap();
+    assert_eq!(melt.rows(&StringRecord::from(vec!["mon", "1", "2", "3"])).len(), 3);
+
+    assert!(Melt::parse("id_cols=date,us,eu,apac", &headers).is_err());
+    assert!(Melt::parse("id_cols=date,value_cols=latam", &headers).is_err());
+    assert!(Melt::parse("ids=date", &headers).is_err());
+    assert!(Melt::parse("date", &headers).is_err());
+}
+
+#[test]
+fn test_explode() {
+    let headers = StringRecord::from(vec!["id", "tags"]);
+    let explode = Explode::parse("tags:|", &headers).unwrap();
+    assert_eq!(explode.rows(&StringRecord::from(vec!["1", "red | blue|green"])), vec![
+        StringRecord::from(vec!["1", "red"]), StringRecord::from(vec!["1", "blue"]), StringRecord::from(vec!["1", "green"]),
+    ]);
+    assert_eq!(explode.rows(&StringRecord::from(vec!["2", ""])), vec![StringRecord::from(vec!["2", ""])]);
+    assert!(Explode::parse("tags:", &headers).is_err());
+    assert!(Explode::parse("labels:|", &headers).is_err());
+
+    let rows = vec![Ok((0, StringRecord::from(vec!["1", "a|b"]))), Ok((1, StringRecord::from(vec!["2", "c"])))];
+    let indices: Vec<usize> = expand(rows.into_iter(), |record| explode.rows(record)).map(|row| row.unwrap().0).collect();
+    assert_eq!(indices, vec![0, 0, 1]);
+}
@@ -0,0 +1,157 @@
+use std::error::Error;
+use csv::StringRecord;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{RngExt, SeedableRng};
+use crate::output::RowWriter;
+
+/// Random generator of --sample, --sample-frac and --shuffle, reproducible
+/// with --seed
+pub fn new_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => rand::make_rng(),
+    }
+}
+
+/// Parses --sample-frac, a fraction between 0 and 1
+pub fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("Expected a fraction between 0 and 1, got '{}'", value)),
+    }
+}
+
+/// Uniform sample of a fixed number of rows from a stream of unknown
+/// length, keeping at most that many rows in memory (reservoir sampling)
+pub struct Reservoir {
+    size: usize,
+    rows_seen: usize,
+    rows: Vec<(usize, StringRecord)>,
+    rng: StdRng,
+}
+
+impl Reservoir {
+    pub fn new(size: usize, rng: StdRng) -> Self {
+        Self { size, rows_seen: 0, rows: Vec::with_capacity(size), rng }
+    }
+
+    /// Adds a record, along with its index in the input
+    pub fn push(&mut self, row_index: usize, record: StringRecord) {
+        self.rows_seen += 1;
+        if self.rows.len() < self.size {
+            self.rows.push((row_index, record));
+            return;
+        }
+        // Each row ends up in the sample with a probability of size / rows_seen
+        let slot = self.rng.random_range(0..self.rows_seen);
+        if slot < self.size {
+            self.rows[slot] = (row_index, record);
+        }
+    }
+
+    /// The sampled rows, in the order of the input
+    pub fn into_rows(mut self) -> Vec<(usize, StringRecord)> {
+        self.rows.sort_by_key(|&(row_index, _)| row_index);
+        self.rows
+    }
+}
+
+/// Passes the rows on in a random order, for --shuffle. The rows are kept
+/// until finish
+pub struct ShuffleWriter {
+    writer: Box<dyn RowWriter>,
+    rows: Vec<Vec<String>>,
+    rng: StdRng,
+}
+
+impl ShuffleWriter {
+    pub fn new(writer: Box<dyn RowWriter>, rng: StdRng) -> Self {
+        Self { writer, rows: Vec::new(), rng }
+    }
+}
+
+impl RowWriter for ShuffleWriter {
+    fn write_header(&mut self, headers: &[String]) -> Result<(), Box<dyn Error>> {
+        self.writer.write_header(headers)
+    }
+
+    fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+        self.rows.push(row.to_vec());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.rows.shuffle(&mut self.rng);
+        for row in self.rows.drain(..) {
+            self.writer.write_row(&row)?;
+        }
+        self.writer.finish()
+    }
+}
+
+#[test]
+fn test_reservoir() {
+    let sample = |seed: u64| {
+        let mut reservoir = Reservoir::new(5, new_rng(Some(seed)));
+        for i in 0..1000 {
+            reservoir.push(i, StringRecord::from(vec![i.to_string()]));
+        }
+        reservoir.into_rows().into_iter().map(|(row_index, _)| row_index).collect::<Vec<usize>>()
+    };
+    let rows = sample(42);
+    assert_eq!(rows.len(), 5);
+    assert!(rows.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(rows, sample(42));
+    assert_ne!(rows, sample(7));
+
+    let mut reservoir = Reservoir::new(5, new_rng(None));
+    reservoir.push(0, StringRecord::new());
+    assert_eq!(reservoir.into_rows().len(), 1);
+}
+
+#[test]
+fn test_parse_fraction() {
+    assert_eq!(parse_fraction("0.01"), Ok(0.01));
+    assert!(parse_fraction("1.5").is_err());
+    assert!(parse_fraction("-0.1").is_err());
+    assert!(parse_fraction("half").is_err());
+}
+
+#[test]
+fn test_shuffle_writer() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Keeps the rows it gets to check them after the shuffle
+    struct Rows(Rc<RefCell<Vec<Vec<String>>>>);
+    impl RowWriter for Rows {
+        fn write_header(&mut self, _headers: &[String]) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+        fn write_row(&mut self, row: &[String]) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(row.to_vec());
+            Ok(())
+        }
+        fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    let shuffle = |seed: u64| {
+        let rows = Rc::new(RefCell::new(Vec::new()));
+        let mut writer = ShuffleWriter::new(Box::new(Rows(Rc::clone(&rows))), new_rng(Some(seed)));
+        for i in 0..20 {
+            writer.write_row(&[i.to_string()]).unwrap();
+        }
+        assert!(rows.borrow().is_empty());
+        writer.finish().unwrap();
+        rows.take().into_iter().map(|row| row[0].parse().unwrap()).collect::<Vec<u32>>()
+    };
+    let rows = shuffle(42);
+    assert_ne!(rows, (0..20).collect::<Vec<u32>>());
+    assert_eq!(rows, shuffle(42));
+    let mut sorted = rows.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec<u32>>());
+}
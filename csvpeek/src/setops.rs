@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::io;
+use csv::StringRecord;
+use crate::columns::column_index;
+use crate::diff::column_order;
+use crate::input::{self, Input, ReaderOptions};
+
+/// Set operations of the union, intersect and except commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperation {
+    /// The rows of both files
+    Union,
+    /// The rows of the first file also in the second one
+    Intersect,
+    /// The rows of the first file not in the second one
+    Except,
+}
+
+/// Passes to on_row the rows of a set operation between two files, each key
+/// once, with the columns in the order of the first file. Without key
+/// columns, the key is the whole row. Only the keys are kept in memory, of
+/// the second file for intersect and except
+pub fn set_rows<F>(operation: SetOperation, first: Input, second: Input, order: &[usize],
+                   key_columns: Option<&[usize]>, mut on_row: F) -> Result<(), Box<dyn Error>>
+where F: FnMut(&StringRecord) -> Result<(), Box<dyn Error>> {
+    let key = |row: &StringRecord| -> Vec<String> {
+        match key_columns {
+            Some(key_columns) => key_columns.iter().map(|&column| String::from(row.get(column).unwrap_or_default())).collect(),
+            None => row.iter().map(String::from).collect(),
+        }
+    };
+    let reordered = |record: StringRecord| -> StringRecord {
+        order.iter().map(|&i| record.get(i).unwrap_or_default()).collect()
+    };
+    let mut seen = HashSet::new();
+    if operation == SetOperation::Union {
+        for record in first.records.chain(second.records.map(|record| record.map(reordered))) {
+            let record = record?;
+            if seen.insert(key(&record)) {
+                on_row(&record)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut second_keys = HashSet::new();
+    for record in second.records {
+        second_keys.insert(key(&reordered(record?)));
+    }
+    for record in first.records {
+        let record = record?;
+        let row_key = key(&record);
+        if second_keys.contains(&row_key) == (operation == SetOperation::Intersect) && seen.insert(row_key) {
+            on_row(&record)?;
+        }
+    }
+    Ok(())
+}
+
+/// The union, intersect and except commands, printing the rows as a CSV
+pub fn run(operation: SetOperation, first_path: &str, second_path: &str, key_str: Option<&str>,
+           options: &ReaderOptions) -> Result<(), Box<dyn Error>> {
+    let first = input::open_input(Some(first_path), options)?;
+    let second = input::open_input(Some(second_path), options)?;
+    let order = column_order(&first.headers, &second.headers, options.has_headers)?;
+    let key_columns = match key_str {
+        Some(key_str) => Some(key_str.split(',')
+            .map(|name| column_index(&first.headers, name, "--key"))
+            .collect::<Result<Vec<usize>, String>>()?),
+        None => None,
+    };
+    let mut writer = csv::Writer::from_writer(io::stdout().lock());
+    if options.has_headers {
+        writer.write_record(&first.headers)?;
+    }
+    set_rows(operation, first, second, &order, key_columns.as_deref(), |row| Ok(writer.write_record(row)?))?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_set_rows() {
+    use crate::input::test_input;
+
+    let set_rows = |operation: SetOperation, key_columns: Option<&[usize]>| {
+        let first = test_input(&[&["id", "name"], &["1", "Ada"], &["2", "Bob"], &["2", "Bob"], &["3", "Cy"]]);
+        let second = test_input(&[&["name", "id"], &["Bob", "2"], &["Dee", "4"], &["Cyril", "3"]]);
+        let mut rows = Vec::new();
+        set_rows(operation, first, second, &[1, 0], key_columns, |row| {
+            rows.push(row.iter().collect::<Vec<&str>>().join(","));
+            Ok(())
+        }).unwrap();
+        rows
+    };
+    assert_eq!(set_rows(SetOperation::Union, None), vec!["1,Ada", "2,Bob", "3,Cy", "4,Dee", "3,Cyril"]);
+    assert_eq!(set_rows(SetOperation::Union, Some(&[0])), vec!["1,Ada", "2,Bob", "3,Cy", "4,Dee"]);
+    assert_eq!(set_rows(SetOperation::Intersect, None), vec!["2,Bob"]);
+    assert_eq!(set_rows(SetOperation::Intersect, Some(&[0])), vec!["2,Bob", "3,Cy"]);
+    assert_eq!(set_rows(SetOperation::Except, None), vec!["1,Ada", "3,Cy"]);
+    assert_eq!(set_rows(SetOperation::Except, Some(&[0])), vec!["1,Ada"]);
+}
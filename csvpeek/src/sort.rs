@@ -0,0 +1,252 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fs::File;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use csv::StringRecord;
+use crate::input::IndexedRecord;
+use crate::value;
+
+/// Column of --sort, like "size:desc"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub column: usize,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Parses a comma separated list of columns, each one optionally
+    /// followed by ":asc" or ":desc". Later columns break the ties
+    pub fn parse_list(sort_str: &str, headers: &StringRecord) -> Result<Vec<Self>, String> {
+        sort_str.split(',')
+            .map(|key_str| {
+                let (name, order) = match key_str.rsplit_once(':') {
+                    Some((name, order @ ("asc" | "desc"))) => (name, order),
+                    _ => (key_str, "asc"),
+                };
+                let column = headers.iter().position(|header| header == name.trim())
+                    .ok_or_else(|| format!("Unknown column '{}' in --sort", name.trim()))?;
+                Ok(SortKey { column, descending: order == "desc" })
+            })
+            .collect()
+    }
+}
+
+/// Compares two records by the keys, with the same type inference as the
+/// filters: as numbers when both fields are numbers, as dates when both are
+/// dates, and as strings otherwise
+pub fn compare(left: &StringRecord, right: &StringRecord, keys: &[SortKey], date_format: Option<&str>) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = value::compare(left.get(key.column).unwrap_or_default(),
+                                          right.get(key.column).unwrap_or_default(), date_format);
+            match key.descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        })
+        .find(|&ordering| ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Bytes of rows kept in memory before they are sorted and spilled to a
+/// temporary file
+const CHUNK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Rows kept until the input ends, and then given back in order. Rows that
+/// compare as equal keep the order of the input. Past CHUNK_SIZE the rows
+/// are sorted in chunks written to temporary files, merged at the end, so
+/// memory stays bounded for inputs of any size
+pub struct Sorter {
+    order: Rc<Order>,
+    chunk_size: usize,
+    rows: Vec<(usize, StringRecord)>,
+    rows_size: usize,
+    chunks: Vec<TempFile>,
+}
+
+struct Order {
+    keys: Vec<SortKey>,
+    date_format: Option<String>,
+}
+
+impl Order {
+    fn compare(&self, left: &(usize, StringRecord), right: &(usize, StringRecord)) -> Ordering {
+        compare(&left.1, &right.1, &self.keys, self.date_format.as_deref()).then(left.0.cmp(&right.0))
+    }
+}
+
+impl Sorter {
+    pub fn new(keys: Vec<SortKey>, date_format: Option<&str>) -> Self {
+        Self::with_chunk_size(keys, date_format, CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(keys: Vec<SortKey>, date_format: Option<&str>, chunk_size: usize) -> Self {
+        Self {
+            order: Rc::new(Order { keys, date_format: date_format.map(String::from) }),
+            chunk_size,
+            rows: Vec::new(),
+            rows_size: 0,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Adds a record, along with its index in the input
+    pub fn push(&mut self, row_index: usize, record: StringRecord) -> Result<(), Box<dyn Error>> {
+        self.rows_size += record.as_slice().len() + record.len() * size_of::<usize>() + size_of::<(usize, StringRecord)>();
+        self.rows.push((row_index, record));
+        if self.rows_size >= self.chunk_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the sorted rows in memory to a temporary file
+    fn spill(&mut self) -> Result<(), Box<dyn Error>> {
+        let chunk = TempFile::new();
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_path(&chunk.0)?;
+        self.rows.sort_by(|left, right| self.order.compare(left, right));
+        for (row_index, record) in self.rows.drain(..) {
+            writer.write_field(row_index.to_string())?;
+            writer.write_record(&record)?;
+        }
+        writer.flush()?;
+        self.chunks.push(chunk);
+        self.rows_size = 0;
+        Ok(())
+    }
+
+    pub fn into_sorted(mut self) -> Result<Box<dyn Iterator<Item = IndexedRecord>>, Box<dyn Error>> {
+        if self.chunks.is_empty() {
+            self.rows.sort_by(|left, right| self.order.compare(left, right));
+            return Ok(Box::new(self.rows.into_iter().map(Ok)));
+        }
+        if !self.rows.is_empty() {
+            self.spill()?;
+        }
+        let mut merge = Merge { heads: BinaryHeap::new(), chunks: Vec::new() };
+        for (i, chunk) in self.chunks.into_iter().enumerate() {
+            let reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_path(&chunk.0)?;
+            merge.chunks.push((reader.into_records(), chunk));
+            merge.advance(i, &self.order)?;
+        }
+        Ok(Box::new(merge))
+    }
+}
+
+/// K-way merge of the sorted chunks, through a heap with the next row of
+/// each one
+struct Merge {
+    heads: BinaryHeap<Head>,
+    chunks: Vec<(csv::StringRecordsIntoIter<File>, TempFile)>,
+}
+
+impl Merge {
+    /// Reads the next row of a chunk into the heap
+    fn advance(&mut self, chunk: usize, order: &Rc<Order>) -> Result<(), Box<dyn Error>> {
+        if let Some(record) = self.chunks[chunk].0.next() {
+            let record = record?;
+            let row_index = record.get(0).and_then(|index| index.parse().ok()).ok_or("Corrupted sort chunk")?;
+            let row = (row_index, record.iter().skip(1).collect());
+            self.heads.push(Head { row, chunk, order: Rc::clone(order) });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = IndexedRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.heads.pop()?;
+        if let Err(err) = self.advance(head.chunk, &head.order) {
+            self.heads.clear();
+            return Some(Err(err));
+        }
+        Some(Ok(head.row))
+    }
+}
+
+struct Head {
+    row: (usize, StringRecord),
+    chunk: usize,
+    order: Rc<Order>,
+}
+
+// BinaryHeap pops the greatest element, so the order is reversed to get
+// the smallest row first
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.order.compare(&other.row, &self.row)
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Temporary file of a sorted chunk, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new() -> Self {
+        static CHUNKS: AtomicUsize = AtomicUsize::new(0);
+        let chunk = CHUNKS.fetch_add(1, AtomicOrdering::Relaxed);
+        Self(std::env::temp_dir().join(format!("csvpeek-sort-{}-{}.csv", std::process::id(), chunk)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn test_sort_key_parse_list() {
+    let headers = StringRecord::from(vec!["name", "size", "time:utc"]);
+    assert_eq!(SortKey::parse_list("size:desc,name", &headers), Ok(vec![
+        SortKey { column: 1, descending: true },
+        SortKey { column: 0, descending: false },
+    ]));
+    assert_eq!(SortKey::parse_list("time:utc:asc", &headers), Ok(vec![SortKey { column: 2, descending: false }]));
+    assert_eq!(SortKey::parse_list("time:utc", &headers), Ok(vec![SortKey { column: 2, descending: false }]));
+    assert!(SortKey::parse_list("weight", &headers).is_err());
+}
+
+#[test]
+fn test_sorter() {
+    let keys = vec![SortKey { column: 1, descending: true }, SortKey { column: 0, descending: false }];
+    let rows = [["b", "9"], ["a", "10"], ["c", "9"], ["d", "x"], ["e", ""]];
+    let mut sorter = Sorter::new(keys.clone(), None);
+    for (i, row) in rows.into_iter().enumerate() {
+        sorter.push(i, StringRecord::from(row.to_vec())).unwrap();
+    }
+    let order: Vec<usize> = sorter.into_sorted().unwrap().map(|row| row.unwrap().0).collect();
+    // "x" is not a number, so it's compared with "10" and "9" as a string
+    assert_eq!(order, vec![3, 1, 0, 2, 4]);
+
+    // Tiny chunks spill almost every row, and merging them gives the same order
+    let mut sorter = Sorter::with_chunk_size(keys, None, 40);
+    for (i, row) in rows.into_iter().enumerate() {
+        sorter.push(i, StringRecord::from(row.to_vec())).unwrap();
+    }
+    assert!(sorter.chunks.len() > 1);
+    let paths: Vec<PathBuf> = sorter.chunks.iter().map(|chunk| chunk.0.clone()).collect();
+    let sorted: Vec<(usize, StringRecord)> = sorter.into_sorted().unwrap().map(Result::unwrap).collect();
+    assert_eq!(sorted.iter().map(|row| row.0).collect::<Vec<usize>>(), order);
+    assert_eq!(sorted[4].1, StringRecord::from(vec!["e", ""]));
+    assert!(paths.iter().all(|path| !path.exists()));
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::BufWriter;
+use std::path::Path;
+use csv::StringRecord;
+use crate::columns::column_index;
+use crate::input::{self, Input, ReaderOptions, STDIN_PATH};
+
+/// Files kept open at once with --by. Past them, the least recently written
+/// one is closed, and opened again to append if its value comes back
+const MAX_OPEN_FILES: usize = 256;
+
+/// How the split command decides where each row goes
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitMode {
+    /// A new file every this many rows
+    Rows(usize),
+    /// A new file before the rows go over about this many bytes
+    Size(u64),
+    /// A file for each value of the column
+    Column(String),
+}
+
+/// Parses a size like "500000", "64K", "10MB" or "1G", in bytes with
+/// 1024-based units
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let multiplier = match value[digits..].trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("Expected a size like 10MB, got '{}'", value)),
+    };
+    match value[..digits].parse::<u64>() {
+        Ok(size) if size > 0 => Ok(size * multiplier),
+        _ => Err(format!("Expected a size like 10MB, got '{}'", value)),
+    }
+}
+
+/// Parses the rows of each file, which can't be 0
+pub fn parse_rows(value: &str) -> Result<usize, String> {
+    match value.trim().parse::<usize>() {
+        Ok(rows) if rows > 0 => Ok(rows),
+        _ => Err(format!("Expected a number of rows above 0, got '{}'", value)),
+    }
+}
+
+/// Name of an output file from the template, replacing {name} by the name
+/// of the input, {n} by the number of the file and {value} by the value of
+/// the column. Characters that don't belong in file names become '_'
+fn file_name(template: &str, name: &str, n: usize, value: &str) -> String {
+    let value = match value {
+        "" => String::from("empty"),
+        value => value.chars().map(|c| if c.is_alphanumeric() || "-_. ".contains(c) { c } else { '_' }).collect(),
+    };
+    template.replace("{name}", name).replace("{n}", &n.to_string()).replace("{value}", &value)
+}
+
+struct Chunk {
+    path: String,
+    // None while closed to make room for other files
+    writer: Option<csv::Writer<BufWriter<File>>>,
+    rows: usize,
+    bytes: u64,
+    // Row of the input last written to the file
+    last_row: usize,
+}
+
+impl Chunk {
+    fn create(path: String, headers: Option<&StringRecord>) -> Result<Self, Box<dyn Error>> {
+        let file = File::create(&path).map_err(|err| format!("Can't create {}: {}", path, err))?;
+        let mut writer = csv::Writer::from_writer(BufWriter::new(file));
+        if let Some(headers) = headers {
+            writer.write_record(headers)?;
+        }
+        Ok(Self { path, writer: Some(writer), rows: 0, bytes: 0, last_row: 0 })
+    }
+
+    fn write(&mut self, record: &StringRecord) -> Result<(), Box<dyn Error>> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let file = OpenOptions::new().append(true).open(&self.path)
+                    .map_err(|err| format!("Can't open {}: {}", self.path, err))?;
+                self.writer.insert(csv::Writer::from_writer(BufWriter::new(file)))
+            },
+        };
+        writer.write_record(record)?;
+        self.rows += 1;
+        // Fields, delimiters and line break, without the quotes
+        self.bytes += (record.as_byte_record().as_slice().len() + record.len().max(1)) as u64;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Closes the least recently written file when too many are open, before
+/// opening another one
+fn make_room(chunks: &mut [Chunk]) -> Result<(), Box<dyn Error>> {
+    if chunks.iter().filter(|chunk| chunk.writer.is_some()).count() < MAX_OPEN_FILES {
+        return Ok(());
+    }
+    match chunks.iter_mut().filter(|chunk| chunk.writer.is_some()).min_by_key(|chunk| chunk.last_row) {
+        Some(chunk) => chunk.close(),
+        None => Ok(()),
+    }
+}
+
+/// Writes the rows of the input to several CSV files, each one with the
+/// header, returning their paths and rows in the order they were created
+pub fn split(input: Input, has_headers: bool, mode: &SplitMode, template: &str,
+             name: &str) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+    let headers = has_headers.then_some(&input.headers);
+    let column = match mode {
+        SplitMode::Column(column_name) => Some(column_index(&input.headers, column_name, "--by")?),
+        _ => None,
+    };
+    // Up to MAX_OPEN_FILES stay open with --by, and only the last one otherwise
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut files = Vec::new();
+    let mut chunk_indices: HashMap<String, usize> = HashMap::new();
+    for (row, record) in input.records.enumerate() {
+        let record = record?;
+        let i = match (mode, column) {
+            (_, Some(column)) => {
+                let value = record.get(column).unwrap_or_default();
+                match chunk_indices.get(value) {
+                    Some(&i) => {
+                        if chunks[i].writer.is_none() {
+                            make_room(&mut chunks)?;
+                        }
+                        i
+                    },
+                    None => {
+                        let path = file_name(template, name, chunks.len() + 1, value);
+                        if chunks.iter().any(|chunk| chunk.path == path) {
+                            return Err(format!("Two values of --by go to the same file {}", path).into());
+                        }
+                        make_room(&mut chunks)?;
+                        chunks.push(Chunk::create(path, headers)?);
+                        chunk_indices.insert(String::from(value), chunks.len() - 1);
+                        chunks.len() - 1
+                    },
+                }
+            },
+            (SplitMode::Rows(rows), _) if chunks.last().is_some_and(|chunk| chunk.rows < *rows) => chunks.len() - 1,
+            (SplitMode::Size(size), _) if chunks.last().is_some_and(|chunk| {
+                chunk.rows == 0 || chunk.bytes + (record.as_byte_record().as_slice().len() + record.len()) as u64 <= *size
+            }) => chunks.len() - 1,
+            _ => {
+                if let Some(mut chunk) = chunks.pop() {
+                    chunk.close()?;
+                    files.push((chunk.path, chunk.rows));
+                }
+                chunks.push(Chunk::create(file_name(template, name, files.len() + 1, ""), headers)?);
+                0
+            },
+        };
+        chunks[i].last_row = row;
+        chunks[i].write(&record)?;
+    }
+    for mut chunk in chunks {
+        chunk.close()?;
+        files.push((chunk.path, chunk.rows));
+    }
+    Ok(files)
+}
+
+/// The split command, printing each file written with its rows
+pub fn run(path: &str, mode: &SplitMode, template: Option<&str>, options: &ReaderOptions) -> Result<(), Box<dyn Error>> {
+    let input = input::open_input(Some(path), options)?;
+    let name = match path {
+        STDIN_PATH => String::from("stdin"),
+        path => Path::new(path).file_name().map_or_else(|| String::from("data"), |name| {
+            String::from(name.to_string_lossy().split('.').next().unwrap_or_default())
+        }),
+    };
+    let template = template.unwrap_or(match mode {
+        SplitMode::Column(_) => "{name}-{value}.csv",
+        _ => "{name}-{n}.csv",
+    });
+    for (path, rows) in split(input, options.has_headers, mode, template, &name)? {
+        println!("{}: {} rows", path, rows);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("500"), Ok(500));
+    assert_eq!(parse_size("64K"), Ok(64 * 1024));
+    assert_eq!(parse_size("10 mb"), Ok(10 * 1024 * 1024));
+    assert_eq!(parse_size("1G"), Ok(1 << 30));
+    assert!(parse_size("0").is_err());
+    assert!(parse_size("10TB").is_err());
+    assert!(parse_size("MB").is_err());
+}
+
+#[test]
+fn test_parse_rows() {
+    assert_eq!(parse_rows("1000"), Ok(1000));
+    assert!(parse_rows("0").is_err());
+    assert!(parse_rows("-5").is_err());
+}
+
+#[test]
+fn test_file_name() {
+    assert_eq!(file_name("{name}-{n}.csv", "sales", 3, ""), "sales-3.csv");
+    assert_eq!(file_name("out/{value}.csv", "sales", 1, "North/East"), "out/North_East.csv");
+    assert_eq!(file_name("{value}.csv", "sales", 1, ""), "empty.csv");
+}
+
+#[test]
+fn test_split() {
+    use crate::input::test_input;
+
+    let dir = std::env::temp_dir().join(format!("csvpeek-split-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let template = format!("{}/{{name}}-{{n}}-{{value}}.csv", dir.display());
+    let rows: &[&[&str]] = &[&["id", "region"], &["1", "north"], &["2", "south"], &["3", "north"]];
+    let read = |path: &str| std::fs::read_to_string(path).unwrap();
+
+    let files = split(test_input(rows), true, &SplitMode::Rows(2), &template, "sales").unwrap();
+    assert_eq!(files.iter().map(|(_, rows)| *rows).collect::<Vec<usize>>(), vec![2, 1]);
+    assert_eq!(read(&files[0].0), "id,region\n1,north\n2,south\n");
+    assert_eq!(read(&files[1].0), "id,region\n3,north\n");
+
+    let files = split(test_input(rows), true, &SplitMode::Column(String::from("region")), &template, "sales").unwrap();
+    assert_eq!(files[0].0, format!("{}/sales-1-north.csv", dir.display()));
+    assert_eq!(read(&files[0].0), "id,region\n1,north\n3,north\n");
+    assert_eq!(read(&files[1].0), "id,region\n2,south\n");
+
+    // Rows of 8 bytes, so two fit in 16
+    let files = split(test_input(rows), false, &SplitMode::Size(16), &template, "sales").unwrap();
+    assert_eq!(files.iter().map(|(_, rows)| *rows).collect::<Vec<usize>>(), vec![2, 1]);
+    assert_eq!(read(&files[1].0), "3,north\n");
+
+    assert!(split(test_input(rows), true, &SplitMode::Column(String::from("city")), &template, "sales").is_err());
+
+    // More values than files kept open, and the first one coming back after
+    // its file was closed
+    let ids: Vec<String> = (0..=MAX_OPEN_FILES).chain([0]).map(|i| i.to_string()).collect();
+    let rows: Vec<Vec<&str>> = std::iter::once(vec!["id", "region"])
+        .chain(ids.iter().map(|id| vec![id.as_str(), "north"]))
+        .collect();
+    let rows: Vec<&[&str]> = rows.iter().map(Vec::as_slice).collect();
+    let files = split(test_input(&rows), true, &SplitMode::Column(String::from("id")), &template, "sales").unwrap();
+    assert_eq!(files.len(), MAX_OPEN_FILES + 1);
+    assert_eq!(read(&files[0].0), "id,region\n0,north\n0,north\n");
+    assert_eq!(files[0].1, 2);
+    assert_eq!(read(&files[MAX_OPEN_FILES].0), format!("id,region\n{},north\n", MAX_OPEN_FILES));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
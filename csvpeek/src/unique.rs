@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use csv::StringRecord;
+use crate::input::IndexedRecord;
+
+/// Row kept of each set of duplicates, for --keep
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Keep {
+    First,
+    Last,
+}
+
+/// Keys of the rows seen so far, for --unique and --unique-by. Without key
+/// columns, the key is the whole row
+pub struct Unique {
+    key_columns: Option<Vec<usize>>,
+    seen: HashSet<Vec<String>>,
+}
+
+impl Unique {
+    pub fn new(key_columns: Option<Vec<usize>>) -> Self {
+        Self { key_columns, seen: HashSet::new() }
+    }
+
+    /// Whether the key of the record is new, remembering it
+    pub fn insert(&mut self, record: &StringRecord) -> bool {
+        let key = key(self.key_columns.as_deref(), record);
+        self.seen.insert(key)
+    }
+
+    /// The last row of each key, in the order of the input. Reads all the
+    /// rows, keeping one per key
+    pub fn last_rows<I>(self, rows: I) -> Result<Vec<(usize, StringRecord)>, Box<dyn Error>>
+    where I: Iterator<Item = IndexedRecord> {
+        let mut last_rows = HashMap::new();
+        for row in rows {
+            let (row_index, record) = row?;
+            last_rows.insert(key(self.key_columns.as_deref(), &record), (row_index, record));
+        }
+        let mut last_rows: Vec<(usize, StringRecord)> = last_rows.into_values().collect();
+        last_rows.sort_by_key(|&(row_index, _)| row_index);
+        Ok(last_rows)
+    }
+}
+
+/// Rows of each key, to report the repeated ones for --find-duplicates.
+/// Without key columns, the key is the whole row
+pub struct Duplicates {
+    key_columns: Option<Vec<usize>>,
+    rows: HashMap<Vec<String>, Vec<usize>>,
+    // Keys in the order they first appear
+    keys: Vec<Vec<String>>,
+}
+
+impl Duplicates {
+    pub fn new(key_columns: Option<Vec<usize>>) -> Self {
+        Self { key_columns, rows: HashMap::new(), keys: Vec::new() }
+    }
+
+    pub fn insert(&mut self, row_index: usize, record: &StringRecord) {
+        let key = key(self.key_columns.as_deref(), record);
+        match self.rows.get_mut(&key) {
+            Some(rows) => rows.push(row_index),
+            None => {
+                self.keys.push(key.clone());
+                self.rows.insert(key, vec![row_index]);
+            },
+        }
+    }
+
+    /// The key columns, or all of them, followed by "count" and "rows"
+    pub fn headers(&self, headers: &StringRecord) -> Vec<String> {
+        let key_headers: Vec<String> = match self.key_columns {
+            Some(ref key_columns) => key_columns.iter().map(|&i| String::from(&headers[i])).collect(),
+            None => headers.iter().map(String::from).collect(),
+        };
+        key_headers.into_iter().chain([String::from("count"), String::from("rows")]).collect()
+    }
+
+    /// Each key found in more than one row, in the order they first appear,
+    /// with the count and the 1-based numbers of its rows, like "2 5 9"
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        self.keys.iter()
+            .filter_map(|key| {
+                let rows = &self.rows[key];
+                (rows.len() > 1).then(|| {
+                    let row_numbers: Vec<String> = rows.iter().map(|row_index| (row_index + 1).to_string()).collect();
+                    key.iter().cloned().chain([rows.len().to_string(), row_numbers.join(" ")]).collect()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Values of the key columns, or the whole row without them
+fn key(key_columns: Option<&[usize]>, record: &StringRecord) -> Vec<String> {
+    match key_columns {
+        Some(key_columns) => key_columns.iter().map(|&i| String::from(record.get(i).unwrap_or_default())).collect(),
+        None => record.iter().map(String::from).collect(),
+    }
+}
+
+#[test]
+fn test_unique() {
+    let records: Vec<StringRecord> = [["1", "a"], ["2", "b"], ["1", "a"], ["1", "c"]].into_iter()
+        .map(|row| StringRecord::from(row.to_vec()))
+        .collect();
+
+    let mut unique = Unique::new(None);
+    let kept: Vec<bool> = records.iter().map(|record| unique.insert(record)).collect();
+    assert_eq!(kept, vec![true, true, false, true]);
+
+    let mut unique = Unique::new(Some(vec![0]));
+    let kept: Vec<bool> = records.iter().map(|record| unique.insert(record)).collect();
+    assert_eq!(kept, vec![true, true, false, false]);
+
+    let rows = records.into_iter().enumerate().map(Ok);
+    let last_rows = Unique::new(Some(vec![0])).last_rows(rows).unwrap();
+    assert_eq!(last_rows, vec![(1, StringRecord::from(vec!["2", "b"])), (3, StringRecord::from(vec!["1", "c"]))]);
+}
+
+#[test]
+fn test_duplicates() {
+    let headers = StringRecord::from(vec!["id", "name"]);
+    let records = [["1", "a"], ["2", "b"], ["1", "a"], ["1", "c"], ["2", "b"]].map(|row| StringRecord::from(row.to_vec()));
+
+    let mut duplicates = Duplicates::new(None);
+    for (row_index, record) in records.iter().enumerate() {
+        duplicates.insert(row_index, record);
+    }
+    assert_eq!(duplicates.headers(&headers), vec!["id", "name", "count", "rows"]);
+    assert_eq!(duplicates.rows(), vec![vec!["1", "a", "2", "1 3"], vec!["2", "b", "2", "2 5"]]);
+
+    let mut duplicates = Duplicates::new(Some(vec![0]));
+    for (row_index, record) in records.iter().enumerate() {
+        duplicates.insert(row_index, record);
+    }
+    assert_eq!(duplicates.headers(&headers), vec!["id", "count", "rows"]);
+    assert_eq!(duplicates.rows(), vec![vec!["1", "3", "1 3 4"], vec!["2", "2", "2 5"]]);
+}
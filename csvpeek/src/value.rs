@@ -0,0 +1,171 @@
+use std::cmp::Ordering;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+/// A CSV field with its inferred type, so numbers are compared as numbers
+/// and dates as dates instead of "10" < "9" string comparisons
+#[derive(Debug, PartialEq)]
+pub enum Value<'a> {
+    Number(f64),
+    Date(NaiveDateTime),
+    Text(&'a str),
+}
+
+impl<'a> Value<'a> {
+    pub fn infer(field: &'a str, date_format: Option<&str>) -> Self {
+        if let Some(number) = parse_number(field) {
+            return Value::Number(number);
+        }
+        match parse_date(field, date_format) {
+            Some(date) => Value::Date(date),
+            None => Value::Text(field),
+        }
+    }
+}
+
+/// Compares two fields as numbers when both are numbers, as dates when both
+/// are dates, and as strings otherwise
+pub fn compare(left: &str, right: &str, date_format: Option<&str>) -> Ordering {
+    match (Value::infer(left, date_format), Value::infer(right, date_format)) {
+        (Value::Number(left_number), Value::Number(right_number)) => left_number.total_cmp(&right_number),
+        (Value::Date(left_date), Value::Date(right_date)) => left_date.cmp(&right_date),
+        _ => left.cmp(right),
+    }
+}
+
+/// Parses a number, ignoring surrounding spaces. "NaN" and "inf" are not numbers
+pub fn parse_number(field: &str) -> Option<f64> {
+    field.trim().parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+/// Parses a date or date time, trying the user format first and then
+/// RFC 3339, "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S" and "%Y-%m-%d".
+/// Dates without time are taken at midnight.
+pub fn parse_date(value: &str, date_format: Option<&str>) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Some(date_format) = date_format {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, date_format) {
+            return Some(date_time);
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(value, date_format) {
+            return date.and_hms_opt(0, 0, 0);
+        }
+    }
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.naive_utc());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date_time);
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)
+}
+
+/// Type of a whole column, the narrowest one that fits all its non-empty
+/// fields. Used by the typed output formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Text,
+}
+
+impl ColumnType {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "int" | "integer" => Ok(ColumnType::Integer),
+            "float" | "double" => Ok(ColumnType::Float),
+            "text" | "string" => Ok(ColumnType::Text),
+            _ => Err(format!("Unknown column type '{}', expected one of: int, float, text", name)),
+        }
+    }
+
+    /// Columns without any value are text
+    pub fn infer<'a>(fields: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut column_type = None;
+        for field in fields {
+            column_type = ColumnType::widen(column_type, field);
+            if column_type == Some(ColumnType::Text) {
+                break;
+            }
+        }
+        column_type.unwrap_or(ColumnType::Text)
+    }
+
+    /// Type of a column after one more field, from its type so far, None
+    /// before any value. Empty fields don't change it
+    pub fn widen(column_type: Option<Self>, field: &str) -> Option<Self> {
+        if field.trim().is_empty() {
+            return column_type;
+        }
+        let field_type = if parse_integer(field).is_some() {
+            ColumnType::Integer
+        } else if parse_number(field).is_some() {
+            ColumnType::Float
+        } else {
+            ColumnType::Text
+        };
+        match (column_type, field_type) {
+            (Some(ColumnType::Text), _) | (_, ColumnType::Text) => Some(ColumnType::Text),
+            (Some(ColumnType::Float), _) | (_, ColumnType::Float) => Some(ColumnType::Float),
+            _ => Some(ColumnType::Integer),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "int",
+            ColumnType::Float => "float",
+            ColumnType::Text => "text",
+        }
+    }
+}
+
+/// Parses a list of "column:type" pairs, like --schema "id:int,price:float"
+pub fn parse_schema(schema_str: &str) -> Result<Vec<(String, ColumnType)>, String> {
+    schema_str.split(',')
+        .map(|column_str| match column_str.rsplit_once(':') {
+            Some((column, type_name)) => Ok((String::from(column.trim()), ColumnType::parse(type_name)?)),
+            None => Err(format!("Expected column:type in schema, got '{}'", column_str)),
+        })
+        .collect()
+}
+
+/// Parses an integer, ignoring surrounding spaces
+pub fn parse_integer(field: &str) -> Option<i64> {
+    field.trim().parse::<i64>().ok()
+}
+
+#[test]
+fn test_value_infer() {
+    assert_eq!(Value::infer("10", None), Value::Number(10.0));
+    assert_eq!(Value::infer(" -2.5 ", None), Value::Number(-2.5));
+    assert_eq!(Value::infer("NaN", None), Value::Text("NaN"));
+    assert_eq!(Value::infer("", None), Value::Text(""));
+    assert_eq!(Value::infer("2024-01-01", None), Value::Date(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+    assert_eq!(Value::infer("01/02/2024", None), Value::Text("01/02/2024"));
+    assert_eq!(Value::infer("01/02/2024", Some("%d/%m/%Y")), Value::Date(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap().and_hms_opt(0, 0, 0).unwrap()));
+}
+
+#[test]
+fn test_value_compare() {
+    // Numbers are not compared as strings
+    assert_eq!(compare("10", "9", None), Ordering::Greater);
+    assert_eq!(compare("3.0", "3", None), Ordering::Equal);
+    assert_eq!(compare("2024-01-02", "2024-01-01T23:00:00", None), Ordering::Greater);
+    // Mixed types fall back to strings
+    assert_eq!(compare("10", "abc", None), Ordering::Less);
+    assert_eq!(compare("b", "a", None), Ordering::Greater);
+}
+
+#[test]
+fn test_column_type() {
+    assert_eq!(ColumnType::infer(["1", "", "-20"]), ColumnType::Integer);
+    assert_eq!(ColumnType::infer(["1", "2.5"]), ColumnType::Float);
+    assert_eq!(ColumnType::infer(["1", "abc", "2.5"]), ColumnType::Text);
+    assert_eq!(ColumnType::infer(["", " "]), ColumnType::Text);
+    assert_eq!(parse_schema("id:int, price:float").unwrap(),
+               vec![(String::from("id"), ColumnType::Integer), (String::from("price"), ColumnType::Float)]);
+    assert!(parse_schema("id:number").is_err());
+    assert!(parse_schema("id").is_err());
+}